@@ -1,23 +1,19 @@
-use imcraft::{Image, Pixel, Uniform};
+use imcraft::{Image, Layer, Layers, Mat3};
 
-fn main() {
-    let tree = &imcraft::BufImage::open("tree.png");
-    let squished = &tree.transform([[0.5, 0.0, 0.0], [0.0, 0.5, 0.0], [0.0, 0.0, 1.0]]);
-    Uniform::new(Pixel {
-        r: 0.0,
-        g: 0.0,
-        b: 0.0,
-        a: 0.0,
-    })
-    .join(tree)
-    .join(squished)
-    .join(squished.translate(100.0, 0.0))
-    .join(squished.translate(200.0, 0.0))
-    .join(squished.translate(300.0, 0.0))
-    .join(
-        squished
-            .transform([[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]])
-            .translate(0.0, 512.0),
-    )
-    .write_to("tree2.png", 512, 512);
+fn main() -> Result<(), imcraft::Error> {
+    let tree = imcraft::BufImage::open("tree.png")?;
+    let tree = &tree;
+    let squished = tree.scale(0.5, 0.5);
+
+    let mut scene = Layers::new();
+    scene.push(Layer::new(tree));
+    scene.push(Layer::new(squished.clone()));
+    for x in [100.0, 200.0, 300.0] {
+        scene.push(Layer::new(squished.clone().then(Mat3::translate(x, 0.0))));
+    }
+    scene.push(Layer::new(
+        squished.then(Mat3::translate(0.0, 512.0) * Mat3::scale(1.0, -1.0)),
+    ));
+    scene.write_to_auto("tree2.png")?;
+    Ok(())
 }
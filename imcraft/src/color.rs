@@ -0,0 +1,69 @@
+use std::sync::OnceLock;
+
+use crate::Pixel;
+
+/// How colour values are encoded outside the pipeline, in source files and
+/// rendered buffers. Inside, images are composited and filtered in linear light.
+///
+/// `Linear` passes values through untouched, which reproduces the old
+/// behaviour of blending encoded sRGB values directly when it is also used as
+/// the blend space of compositors and layers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub(crate) fn decode_u8(self, value: u8) -> f32 {
+        match self {
+            ColorSpace::Srgb => {
+                static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+                TABLE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear(i as f32 / 255.0)))
+                    [value as usize]
+            }
+            ColorSpace::Linear => value as f32 / 255.0,
+        }
+    }
+
+    pub(crate) fn decode(self, pixel: Pixel) -> Pixel {
+        match self {
+            ColorSpace::Srgb => Pixel {
+                r: srgb_to_linear(pixel.r),
+                g: srgb_to_linear(pixel.g),
+                b: srgb_to_linear(pixel.b),
+                a: pixel.a,
+            },
+            ColorSpace::Linear => pixel,
+        }
+    }
+
+    pub(crate) fn encode(self, pixel: Pixel) -> Pixel {
+        match self {
+            ColorSpace::Srgb => Pixel {
+                r: linear_to_srgb(pixel.r),
+                g: linear_to_srgb(pixel.g),
+                b: linear_to_srgb(pixel.b),
+                a: pixel.a,
+            },
+            ColorSpace::Linear => pixel,
+        }
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
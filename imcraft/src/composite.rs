@@ -0,0 +1,411 @@
+use crate::rect;
+use crate::{ColorSpace, Image, Pixel, PremulPixel, Rect};
+
+/// Porter-Duff operators, combining a source drawn onto a destination (backdrop).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Operator {
+    Clear,
+    Src,
+    Dst,
+    #[default]
+    SrcOver,
+    DstOver,
+    SrcIn,
+    DstIn,
+    SrcOut,
+    DstOut,
+    SrcAtop,
+    DstAtop,
+    Xor,
+}
+
+impl Operator {
+    /// The fractions of source and destination that survive, given both alphas.
+    fn factors(self, src_a: f32, dst_a: f32) -> (f32, f32) {
+        match self {
+            Operator::Clear => (0.0, 0.0),
+            Operator::Src => (1.0, 0.0),
+            Operator::Dst => (0.0, 1.0),
+            Operator::SrcOver => (1.0, 1.0 - src_a),
+            Operator::DstOver => (1.0 - dst_a, 1.0),
+            Operator::SrcIn => (dst_a, 0.0),
+            Operator::DstIn => (0.0, src_a),
+            Operator::SrcOut => (1.0 - dst_a, 0.0),
+            Operator::DstOut => (0.0, 1.0 - src_a),
+            Operator::SrcAtop => (dst_a, 1.0 - src_a),
+            Operator::DstAtop => (1.0 - dst_a, src_a),
+            Operator::Xor => (1.0 - dst_a, 1.0 - src_a),
+        }
+    }
+
+    /// Where the result can be visible, given where each input is.
+    fn bounds(self, dst: Option<Rect>, src: Option<Rect>) -> Option<Rect> {
+        match self {
+            Operator::Clear => Some(Rect::default()),
+            Operator::Src | Operator::SrcOut | Operator::DstAtop => src,
+            Operator::Dst | Operator::DstOut | Operator::SrcAtop => dst,
+            Operator::SrcIn | Operator::DstIn => rect::intersect(dst, src),
+            Operator::SrcOver | Operator::DstOver | Operator::Xor => rect::union(dst, src),
+        }
+    }
+}
+
+/// The W3C Compositing and Blending Level 1 blend modes, which decide the
+/// colour where source and destination overlap. As in CSS and image editors,
+/// the formulas see encoded values, sRGB unless the compositor is given another
+/// blend space; only the result returns to linear light.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    /// Blends straight-alpha backdrop and source colours.
+    fn blend(self, cb: [f32; 3], cs: [f32; 3]) -> [f32; 3] {
+        let separable =
+            |f: fn(f32, f32) -> f32| [f(cb[0], cs[0]), f(cb[1], cs[1]), f(cb[2], cs[2])];
+        match self {
+            BlendMode::Normal => cs,
+            BlendMode::Multiply => separable(multiply),
+            BlendMode::Screen => separable(screen),
+            BlendMode::Overlay => separable(|b, s| hard_light(s, b)),
+            BlendMode::Darken => separable(f32::min),
+            BlendMode::Lighten => separable(f32::max),
+            BlendMode::ColorDodge => separable(|b, s| {
+                if b == 0.0 {
+                    0.0
+                } else if s >= 1.0 {
+                    1.0
+                } else {
+                    (b / (1.0 - s)).min(1.0)
+                }
+            }),
+            BlendMode::ColorBurn => separable(|b, s| {
+                if b >= 1.0 {
+                    1.0
+                } else if s <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - b) / s).min(1.0)
+                }
+            }),
+            BlendMode::HardLight => separable(hard_light),
+            BlendMode::SoftLight => separable(|b, s| {
+                if s <= 0.5 {
+                    b - (1.0 - 2.0 * s) * b * (1.0 - b)
+                } else {
+                    let d = if b <= 0.25 {
+                        ((16.0 * b - 12.0) * b + 4.0) * b
+                    } else {
+                        b.sqrt()
+                    };
+                    b + (2.0 * s - 1.0) * (d - b)
+                }
+            }),
+            BlendMode::Difference => separable(|b, s| (b - s).abs()),
+            BlendMode::Exclusion => separable(|b, s| b + s - 2.0 * b * s),
+            BlendMode::Hue => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+            BlendMode::Saturation => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+            BlendMode::Color => set_lum(cs, lum(cb)),
+            BlendMode::Luminosity => set_lum(cb, lum(cs)),
+        }
+    }
+}
+
+fn multiply(b: f32, s: f32) -> f32 {
+    b * s
+}
+
+fn screen(b: f32, s: f32) -> f32 {
+    b + s - b * s
+}
+
+fn hard_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        multiply(b, 2.0 * s)
+    } else {
+        screen(b, 2.0 * s - 1.0)
+    }
+}
+
+fn lum([r, g, b]: [f32; 3]) -> f32 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn clip_color(c: [f32; 3]) -> [f32; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    c.map(|v| {
+        let mut v = v;
+        if n < 0.0 {
+            v = l + (v - l) * l / (l - n);
+        }
+        if x > 1.0 {
+            v = l + (v - l) * (1.0 - l) / (x - l);
+        }
+        v
+    })
+}
+
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|v| v + d))
+}
+
+fn sat([r, g, b]: [f32; 3]) -> f32 {
+    r.max(g).max(b) - r.min(g).min(b)
+}
+
+fn set_sat(c: [f32; 3], s: f32) -> [f32; 3] {
+    let max = c[0].max(c[1]).max(c[2]);
+    let min = c[0].min(c[1]).min(c[2]);
+    if max <= min {
+        return [0.0; 3];
+    }
+    c.map(|v| (v - min) * s / (max - min))
+}
+
+pub(crate) fn composite(
+    dst: PremulPixel,
+    src: PremulPixel,
+    op: Operator,
+    mode: BlendMode,
+    space: ColorSpace,
+) -> PremulPixel {
+    let src = if mode == BlendMode::Normal || dst.a == 0.0 || src.a == 0.0 {
+        src
+    } else {
+        // Where the backdrop is opaque the source colour is replaced by the
+        // blended colour; elsewhere it shows through unchanged.
+        let cb = space.encode(dst.unpremultiply());
+        let cs = space.encode(src.unpremultiply());
+        let [r, g, b] = mode.blend([cb.r, cb.g, cb.b], [cs.r, cs.g, cs.b]);
+        let blended = space.decode(Pixel { r, g, b, a: 1.0 });
+        let blended = [blended.r, blended.g, blended.b];
+        let mix = |s: f32, b: f32| (1.0 - dst.a) * s + src.a * dst.a * b;
+        PremulPixel {
+            r: mix(src.r, blended[0]),
+            g: mix(src.g, blended[1]),
+            b: mix(src.b, blended[2]),
+            a: src.a,
+        }
+    };
+    let (fa, fb) = op.factors(src.a, dst.a);
+    src * fa + dst * fb
+}
+
+pub struct Composite<D, S> {
+    dst: D,
+    src: S,
+    op: Operator,
+    mode: BlendMode,
+    space: ColorSpace,
+}
+
+impl<D: Image, S: Image> Composite<D, S> {
+    pub(crate) fn new(dst: D, src: S, op: Operator, mode: BlendMode) -> Self {
+        Self {
+            dst,
+            src,
+            op,
+            mode,
+            space: ColorSpace::Srgb,
+        }
+    }
+
+    /// Sets the encoding the blend formulas see. Pass [`ColorSpace::Linear`]
+    /// together with linear sources and output to keep the legacy results.
+    pub fn with_blend_space(mut self, space: ColorSpace) -> Self {
+        self.space = space;
+        self
+    }
+}
+
+impl<D: Image, S: Image> Image for Composite<D, S> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        let dst = self.dst.sample(x, y, footprint);
+        let src = self.src.sample(x, y, footprint);
+        composite(dst, src, self.op, self.mode, self.space)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        self.op.bounds(self.dst.bounds(), self.src.bounds())
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        self.op.bounds(self.dst.extent(), self.src.extent())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKDROP: [f32; 3] = [0.2, 0.5, 0.8];
+    const SOURCE: [f32; 3] = [0.6, 0.3, 0.5];
+
+    /// Expected results on the encoded values above, worked from the W3C
+    /// formulas.
+    const TABLE: [(BlendMode, [f32; 3]); 16] = [
+        (BlendMode::Normal, [0.6, 0.3, 0.5]),
+        (BlendMode::Multiply, [0.12, 0.15, 0.4]),
+        (BlendMode::Screen, [0.68, 0.65, 0.9]),
+        (BlendMode::Overlay, [0.24, 0.3, 0.8]),
+        (BlendMode::Darken, [0.2, 0.3, 0.5]),
+        (BlendMode::Lighten, [0.6, 0.5, 0.8]),
+        (BlendMode::ColorDodge, [0.5, 0.714_286, 1.0]),
+        (BlendMode::ColorBurn, [0.0, 0.0, 0.6]),
+        (BlendMode::HardLight, [0.36, 0.3, 0.8]),
+        (BlendMode::SoftLight, [0.2496, 0.4, 0.8]),
+        (BlendMode::Difference, [0.4, 0.2, 0.3]),
+        (BlendMode::Exclusion, [0.56, 0.5, 0.5]),
+        (BlendMode::Hue, [0.819, 0.219, 0.619]),
+        (BlendMode::Saturation, [0.3215, 0.4715, 0.6215]),
+        (BlendMode::Color, [0.631, 0.331, 0.531]),
+        (BlendMode::Luminosity, [0.169, 0.469, 0.769]),
+    ];
+
+    fn opaque([r, g, b]: [f32; 3]) -> PremulPixel {
+        Pixel::from_srgb(r, g, b, 1.0).premultiply()
+    }
+
+    #[test]
+    fn blend_modes_match_the_spec_on_encoded_values() {
+        for (mode, expected) in TABLE {
+            let out = composite(
+                opaque(BACKDROP),
+                opaque(SOURCE),
+                Operator::SrcOver,
+                mode,
+                ColorSpace::Srgb,
+            );
+            let out = ColorSpace::Srgb.encode(out.unpremultiply());
+            for (got, want) in [out.r, out.g, out.b].into_iter().zip(expected) {
+                assert!(
+                    (got - want).abs() < 1e-3,
+                    "{mode:?}: {out:?} != {expected:?}"
+                );
+            }
+            assert_eq!(out.a, 1.0);
+        }
+    }
+
+    #[test]
+    fn blending_over_transparency_keeps_the_source() {
+        let src = opaque(SOURCE) * 0.5;
+        for (mode, _) in TABLE {
+            let out = composite(
+                PremulPixel::default(),
+                src,
+                Operator::SrcOver,
+                mode,
+                ColorSpace::Srgb,
+            );
+            assert_eq!(out, src, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn linear_blend_space_keeps_the_legacy_results() {
+        use crate::{Layer, Layers, RenderOptions, Uniform};
+
+        let grey = || {
+            Uniform::new(Pixel {
+                r: 0.5,
+                g: 0.5,
+                b: 0.5,
+                a: 1.0,
+            })
+        };
+        let options = RenderOptions {
+            color_space: ColorSpace::Linear,
+            ..RenderOptions::default()
+        };
+        for (mode, expected) in [(BlendMode::Screen, 191), (BlendMode::Multiply, 64)] {
+            let blended = grey()
+                .blend(grey(), mode)
+                .with_blend_space(ColorSpace::Linear);
+            assert_eq!(
+                blended.render_with(1, 1, &options),
+                [expected, expected, expected, 255]
+            );
+
+            let stack: Layers = [
+                Layer::new(grey()),
+                Layer::new(grey())
+                    .with_blend(mode)
+                    .with_blend_space(ColorSpace::Linear),
+            ]
+            .into_iter()
+            .collect();
+            assert_eq!(
+                stack.render_with(1, 1, &options),
+                [expected, expected, expected, 255]
+            );
+        }
+    }
+
+    #[test]
+    fn operators_match_porter_duff() {
+        let dst = PremulPixel {
+            r: 0.5,
+            g: 0.0,
+            b: 0.0,
+            a: 0.5,
+        };
+        let src = PremulPixel {
+            r: 0.0,
+            g: 0.0,
+            b: 0.25,
+            a: 0.25,
+        };
+        let (under, over) = (Rect::new(0.0, 0.0, 4.0, 4.0), Rect::new(2.0, 2.0, 4.0, 4.0));
+        let (union, overlap) = (Rect::new(0.0, 0.0, 6.0, 6.0), Rect::new(2.0, 2.0, 2.0, 2.0));
+        let table = [
+            (Operator::Clear, [0.0, 0.0, 0.0, 0.0], Rect::default()),
+            (Operator::Src, [0.0, 0.0, 0.25, 0.25], over),
+            (Operator::Dst, [0.5, 0.0, 0.0, 0.5], under),
+            (Operator::SrcOver, [0.375, 0.0, 0.25, 0.625], union),
+            (Operator::DstOver, [0.5, 0.0, 0.125, 0.625], union),
+            (Operator::SrcIn, [0.0, 0.0, 0.125, 0.125], overlap),
+            (Operator::DstIn, [0.125, 0.0, 0.0, 0.125], overlap),
+            (Operator::SrcOut, [0.0, 0.0, 0.125, 0.125], over),
+            (Operator::DstOut, [0.375, 0.0, 0.0, 0.375], under),
+            (Operator::SrcAtop, [0.375, 0.0, 0.125, 0.5], under),
+            (Operator::DstAtop, [0.125, 0.0, 0.125, 0.25], over),
+            (Operator::Xor, [0.375, 0.0, 0.125, 0.5], union),
+        ];
+        for (op, [r, g, b, a], bounds) in table {
+            let out = composite(dst, src, op, BlendMode::Normal, ColorSpace::Srgb);
+            let expected = PremulPixel { r, g, b, a };
+            assert_eq!(out, expected, "{op:?}");
+            assert_eq!(op.bounds(Some(under), Some(over)), Some(bounds), "{op:?}");
+        }
+
+        // An unbounded side passes through where the other one is not needed.
+        assert_eq!(Operator::SrcIn.bounds(None, Some(over)), Some(over));
+        assert_eq!(Operator::SrcOver.bounds(None, Some(over)), None);
+        assert_eq!(Operator::DstOut.bounds(Some(under), None), Some(under));
+    }
+}
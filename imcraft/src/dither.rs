@@ -0,0 +1,318 @@
+use std::sync::OnceLock;
+
+/// How values are spread over the available levels when quantizing to an
+/// integer format, trading banding for fine noise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    #[default]
+    None,
+    /// Ordered dithering with an 8 × 8 Bayer matrix.
+    Bayer,
+    /// Ordered dithering with a 64 × 64 void-and-cluster blue noise mask.
+    BlueNoise,
+    FloydSteinberg,
+    /// Atkinson's error diffusion, which drops a quarter of the error for
+    /// higher contrast.
+    Atkinson,
+}
+
+impl Dither {
+    /// Whether quantizing a row depends on the rows before it.
+    pub(crate) fn is_diffusion(self) -> bool {
+        matches!(self, Dither::FloydSteinberg | Dither::Atkinson)
+    }
+
+    /// The ordered dither threshold in `(0, 1)` for a pixel, if any.
+    fn threshold(self, x: usize, y: usize) -> Option<f32> {
+        match self {
+            Dither::Bayer => Some((bayer(x % 8, y % 8) as f32 + 0.5) / 64.0),
+            Dither::BlueNoise => {
+                Some(blue_noise()[y % BLUE_NOISE_SIZE * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE])
+            }
+            _ => None,
+        }
+    }
+
+    /// The error diffusion kernel as `(dx, dy, weight)` taps.
+    fn kernel(self) -> &'static [(isize, usize, f32)] {
+        match self {
+            Dither::FloydSteinberg => &[
+                (1, 0, 7.0 / 16.0),
+                (-1, 1, 3.0 / 16.0),
+                (0, 1, 5.0 / 16.0),
+                (1, 1, 1.0 / 16.0),
+            ],
+            Dither::Atkinson => &[
+                (1, 0, 0.125),
+                (2, 0, 0.125),
+                (-1, 1, 0.125),
+                (0, 1, 0.125),
+                (1, 1, 0.125),
+                (0, 2, 0.125),
+            ],
+            _ => &[],
+        }
+    }
+}
+
+/// Quantizes rows in order, carrying diffused error from one row to the next.
+pub(crate) struct Ditherer {
+    dither: Dither,
+    next_row: usize,
+    /// Error owed to this row and the two below it.
+    errors: [Vec<[f32; 4]>; 3],
+}
+
+impl Ditherer {
+    pub(crate) fn new(dither: Dither, width: usize) -> Self {
+        let len = if dither.is_diffusion() { width } else { 0 };
+        Self {
+            dither,
+            next_row: 0,
+            errors: std::array::from_fn(|_| vec![[0.0; 4]; len]),
+        }
+    }
+
+    /// Replaces the values of row `y`, left to right, with ones picked by
+    /// `quantize`, whose levels are roughly `step` apart. Diffused error is only carried over
+    /// when rows arrive in order.
+    pub(crate) fn dither_row(
+        &mut self,
+        y: usize,
+        row: &mut [[f32; 4]],
+        step: f32,
+        mut quantize: impl FnMut([f32; 4]) -> [f32; 4],
+    ) {
+        if !self.dither.is_diffusion() {
+            for (x, value) in row.iter_mut().enumerate() {
+                let offset = self
+                    .dither
+                    .threshold(x, y)
+                    .map_or(0.0, |t| (t - 0.5) * step);
+                *value = quantize(value.map(|v| v + offset));
+            }
+            return;
+        }
+
+        if y != self.next_row {
+            self.errors.iter_mut().for_each(|e| e.fill([0.0; 4]));
+        }
+        self.next_row = y + 1;
+        let kernel = self.dither.kernel();
+        for (x, value) in row.iter_mut().enumerate() {
+            let error = self.errors[0][x];
+            let wanted: [f32; 4] = std::array::from_fn(|c| value[c] + error[c]);
+            *value = quantize(wanted);
+            for &(dx, dy, weight) in kernel {
+                let Some(target) = self.errors[dy].get_mut(x.wrapping_add_signed(dx)) else {
+                    continue;
+                };
+                for c in 0..4 {
+                    target[c] += (wanted[c] - value[c]) * weight;
+                }
+            }
+        }
+        self.errors.rotate_left(1);
+        self.errors[2].fill([0.0; 4]);
+    }
+}
+
+/// Entry `(x, y)` of the 8 × 8 Bayer matrix, in `0..64`.
+fn bayer(x: usize, y: usize) -> usize {
+    (0..3).fold(0, |v, bit| {
+        let (xb, yb) = ((x >> bit) & 1, (y >> bit) & 1);
+        v << 2 | ((xb ^ yb) << 1 | yb)
+    })
+}
+
+const BLUE_NOISE_SIZE: usize = 64;
+
+fn blue_noise() -> &'static [f32] {
+    static TABLE: OnceLock<Vec<f32>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let ranks = void_and_cluster(BLUE_NOISE_SIZE);
+        let len = ranks.len() as f32;
+        ranks.into_iter().map(|r| (r as f32 + 0.5) / len).collect()
+    })
+}
+
+/// Ranks the cells of an `n × n` torus with Ulichney's void-and-cluster
+/// method, so that every threshold of the ranks is an evenly spread pattern.
+fn void_and_cluster(n: usize) -> Vec<usize> {
+    let len = n * n;
+    let mut pattern = Pattern::new(n);
+
+    // A sparse random start, relaxed by moving the tightest cluster into the
+    // largest void until that stops changing anything.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut count = 0;
+    while count < len / 10 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let p = (state % len as u64) as usize;
+        if !pattern.ones[p] {
+            pattern.set(p, true);
+            count += 1;
+        }
+    }
+    loop {
+        let cluster = pattern.extreme(true);
+        pattern.set(cluster, false);
+        let void = pattern.extreme(false);
+        pattern.set(void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; len];
+    let initial = (pattern.ones.clone(), pattern.energy.clone());
+    for rank in (0..count).rev() {
+        let cluster = pattern.extreme(true);
+        pattern.set(cluster, false);
+        ranks[cluster] = rank;
+    }
+    (pattern.ones, pattern.energy) = initial;
+    for rank in count..len {
+        let void = pattern.extreme(false);
+        pattern.set(void, true);
+        ranks[void] = rank;
+    }
+    ranks
+}
+
+/// A binary pattern on a torus with its Gaussian-filtered density.
+struct Pattern {
+    n: usize,
+    kernel: Vec<f32>,
+    ones: Vec<bool>,
+    energy: Vec<f32>,
+}
+
+impl Pattern {
+    fn new(n: usize) -> Self {
+        let kernel = (0..n * n)
+            .map(|i| {
+                let (dx, dy) = (i % n, i / n);
+                let (dx, dy) = (dx.min(n - dx) as f32, dy.min(n - dy) as f32);
+                (-(dx * dx + dy * dy) / 4.5).exp()
+            })
+            .collect();
+        Self {
+            n,
+            kernel,
+            ones: vec![false; n * n],
+            energy: vec![0.0; n * n],
+        }
+    }
+
+    fn set(&mut self, p: usize, one: bool) {
+        self.ones[p] = one;
+        let sign = if one { 1.0 } else { -1.0 };
+        let (n, px, py) = (self.n, p % self.n, p / self.n);
+        for (y, row) in self.energy.chunks_exact_mut(n).enumerate() {
+            let dy = (y + n - py) % n;
+            for (x, e) in row.iter_mut().enumerate() {
+                *e += sign * self.kernel[dy * n + (x + n - px) % n];
+            }
+        }
+    }
+
+    /// The densest one, or the emptiest zero.
+    fn extreme(&self, one: bool) -> usize {
+        let cells = (0..self.ones.len()).filter(|&p| self.ones[p] == one);
+        let energy = |p: &usize| self.energy[*p];
+        if one {
+            cells
+                .max_by(|a, b| energy(a).total_cmp(&energy(b)))
+                .unwrap()
+        } else {
+            cells
+                .min_by(|a, b| energy(a).total_cmp(&energy(b)))
+                .unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_permutation(ranks: &[usize]) -> bool {
+        let mut sorted = ranks.to_vec();
+        sorted.sort_unstable();
+        sorted.iter().copied().eq(0..ranks.len())
+    }
+
+    #[test]
+    fn bayer_matrix_is_the_standard_one() {
+        let rows: Vec<Vec<usize>> = (0..2)
+            .map(|y| (0..8).map(|x| bayer(x, y)).collect())
+            .collect();
+        assert_eq!(rows[0], [0, 32, 8, 40, 2, 34, 10, 42]);
+        assert_eq!(rows[1], [48, 16, 56, 24, 50, 18, 58, 26]);
+        let all: Vec<_> = (0..64).map(|i| bayer(i % 8, i / 8)).collect();
+        assert!(is_permutation(&all));
+    }
+
+    #[test]
+    fn void_and_cluster_ranks_every_cell_once() {
+        assert!(is_permutation(&void_and_cluster(16)));
+        let table = blue_noise();
+        assert_eq!(table.len(), BLUE_NOISE_SIZE * BLUE_NOISE_SIZE);
+        assert!(table.iter().all(|&t| t > 0.0 && t < 1.0));
+    }
+
+    #[test]
+    fn void_and_cluster_spreads_each_threshold_evenly() {
+        let n = 16;
+        let ranks = void_and_cluster(n);
+        let wrap = |u: usize, v: usize| u.abs_diff(v).min(n - u.abs_diff(v));
+        let nearest = |count: usize| -> Vec<usize> {
+            let points: Vec<_> = (0..n * n).filter(|&p| ranks[p] < count).collect();
+            points
+                .iter()
+                .map(|&a| {
+                    let others = points.iter().filter(|&&b| b != a);
+                    others
+                        .map(|&b| wrap(a % n, b % n).pow(2) + wrap(a / n, b / n).pow(2))
+                        .min()
+                        .unwrap()
+                })
+                .collect()
+        };
+
+        // Sparse thresholds keep every point at least two cells from the next.
+        assert!(nearest(n * n / 10).iter().all(|&d| d >= 4));
+        // A random quarter of the cells would give about two thirds of its
+        // points an edge neighbour; blue noise keeps that well under half.
+        let dense = nearest(n * n / 4);
+        let touching = dense.iter().filter(|&&d| d == 1).count();
+        assert!(
+            touching * 2 < dense.len(),
+            "{touching} of {} touch",
+            dense.len()
+        );
+    }
+
+    #[test]
+    fn dithering_preserves_the_mean() {
+        let (width, height, value, step) = (64, 64, 0.3, 0.25);
+        let quantize = |v: [f32; 4]| v.map(|c| ((c / step).round() * step).clamp(0.0, 1.0));
+        // Atkinson drops a quarter of the error on purpose, so only the others
+        // keep the average.
+        for dither in [Dither::Bayer, Dither::BlueNoise, Dither::FloydSteinberg] {
+            let mut ditherer = Ditherer::new(dither, width);
+            let mut sum = 0.0;
+            for y in 0..height {
+                let mut row = vec![[value; 4]; width];
+                ditherer.dither_row(y, &mut row, step, quantize);
+                assert!(row.iter().flatten().all(|&c| c == 0.25 || c == 0.5));
+                sum += row.iter().map(|v| v[0]).sum::<f32>();
+            }
+            let mean = sum / (width * height) as f32;
+            assert!((mean - value).abs() < 0.01, "{dither:?} mean {mean}");
+        }
+    }
+}
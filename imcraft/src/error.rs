@@ -0,0 +1,148 @@
+use std::path::Path;
+use std::{fmt, io};
+
+use image::error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{ImageError, ImageFormat};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Decode(ImageError),
+    UnsupportedFormat(UnsupportedError),
+    Encode(ImageError),
+    InvalidDimensions {
+        width: usize,
+        height: usize,
+    },
+    /// The image has no bounds, so there is no extent to render.
+    Unbounded,
+}
+
+impl Error {
+    /// Checks that a raster of this size can be encoded.
+    pub(crate) fn check_dimensions(width: usize, height: usize) -> Result<(u32, u32), Error> {
+        let invalid = || Error::InvalidDimensions { width, height };
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        let w = u32::try_from(width).map_err(|_| invalid())?;
+        let h = u32::try_from(height).map_err(|_| invalid())?;
+        Ok((w, h))
+    }
+
+    /// Maps a decoder failure. Read errors surfacing from inside the decoder,
+    /// such as a truncated stream, mean the data is bad rather than the file,
+    /// so only [`Error::UnsupportedFormat`] is split out.
+    pub(crate) fn decode(err: ImageError) -> Error {
+        match err {
+            ImageError::Unsupported(err) => Error::UnsupportedFormat(err),
+            err => Error::Decode(err),
+        }
+    }
+
+    pub(crate) fn encode(err: ImageError) -> Error {
+        match err {
+            ImageError::IoError(err) => Error::Io(err),
+            ImageError::Unsupported(err) => Error::UnsupportedFormat(err),
+            err => Error::Encode(err),
+        }
+    }
+
+    /// Reports that a writer cannot produce the format `path` asks for. A
+    /// recognised extension names its format exactly, since the format is
+    /// known but unsupported here.
+    pub(crate) fn unsupported_output(path: &Path) -> Error {
+        let hint = match (ImageFormat::from_path(path), path.extension()) {
+            (Ok(format), _) => ImageFormatHint::Exact(format),
+            (Err(_), Some(ext)) => ImageFormatHint::PathExtension(ext.into()),
+            (Err(_), None) => ImageFormatHint::Unknown,
+        };
+        Error::UnsupportedFormat(UnsupportedError::from_format_and_kind(
+            hint.clone(),
+            UnsupportedErrorKind::Format(hint),
+        ))
+    }
+
+    fn encoder(format: ImageFormat, err: impl std::error::Error + Send + Sync + 'static) -> Error {
+        Error::Encode(ImageError::Encoding(EncodingError::new(format.into(), err)))
+    }
+
+    pub(crate) fn png(err: png::EncodingError) -> Error {
+        match err {
+            png::EncodingError::IoError(err) => Error::Io(err),
+            err => Error::encoder(ImageFormat::Png, err),
+        }
+    }
+
+    pub(crate) fn tiff(err: tiff::TiffError) -> Error {
+        match err {
+            tiff::TiffError::IoError(err) => Error::Io(err),
+            err => Error::encoder(ImageFormat::Tiff, err),
+        }
+    }
+
+    pub(crate) fn gif(err: gif::EncodingError) -> Error {
+        match err {
+            gif::EncodingError::Io(err) => Error::Io(err),
+            err => Error::encoder(ImageFormat::Gif, err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "i/o error: {err}"),
+            Error::Decode(err) => write!(f, "failed to decode image: {err}"),
+            Error::UnsupportedFormat(err) => write!(f, "unsupported format: {err}"),
+            Error::Encode(err) => write!(f, "failed to encode image: {err}"),
+            Error::InvalidDimensions { width, height } => {
+                write!(f, "invalid image dimensions {width}x{height}")
+            }
+            Error::Unbounded => write!(f, "image is unbounded"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Decode(err) | Error::Encode(err) => Some(err),
+            Error::UnsupportedFormat(err) => Some(err),
+            Error::InvalidDimensions { .. } | Error::Unbounded => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hint(path: &str) -> ImageFormatHint {
+        match Error::unsupported_output(Path::new(path)) {
+            Error::UnsupportedFormat(err) => {
+                assert!(matches!(err.kind(), UnsupportedErrorKind::Format(_)));
+                err.format_hint()
+            }
+            err => panic!("unexpected error {err}"),
+        }
+    }
+
+    #[test]
+    fn known_formats_are_named_exactly() {
+        assert_eq!(hint("out.jpg"), ImageFormatHint::Exact(ImageFormat::Jpeg));
+        assert_eq!(hint("out.WEBP"), ImageFormatHint::Exact(ImageFormat::WebP));
+        assert_eq!(
+            hint("out.xyz"),
+            ImageFormatHint::PathExtension("xyz".into())
+        );
+        assert_eq!(hint("out"), ImageFormatHint::Unknown);
+    }
+}
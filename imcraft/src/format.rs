@@ -0,0 +1,194 @@
+use image::{Luma, Rgb, Rgba};
+
+use crate::{ColorSpace, PremulPixel};
+
+/// A pixel type [`Image::render_into`](crate::Image::render_into) can write.
+///
+/// Integer channels are clamped to `[0, 1]`, then rounded (or dithered) to a
+/// level; `f32` channels keep values outside that range. Formats without
+/// alpha are composited over black.
+pub trait PixelFormat: image::Pixel {
+    /// The largest value of an integer channel, or `None` for float formats,
+    /// which are neither clamped nor dithered.
+    const MAX: Option<f32>;
+
+    /// The encoded channel values, nominally in `[0, 1]`. Only the first
+    /// `CHANNEL_COUNT` are used.
+    fn encode(pixel: PremulPixel, color_space: ColorSpace) -> [f32; 4];
+
+    /// Builds a pixel from encoded channels, rounding and clamping integer channels.
+    fn from_encoded(channels: [f32; 4]) -> Self;
+}
+
+fn quantize(v: f32, max: f32) -> f32 {
+    (v.clamp(0.0, 1.0) * max).round()
+}
+
+impl PixelFormat for Rgba<u8> {
+    const MAX: Option<f32> = Some(255.0);
+
+    fn encode(pixel: PremulPixel, color_space: ColorSpace) -> [f32; 4] {
+        let p = color_space.encode(pixel.unpremultiply());
+        [p.r, p.g, p.b, p.a]
+    }
+
+    fn from_encoded(channels: [f32; 4]) -> Self {
+        Rgba(channels.map(|v| quantize(v, 255.0) as u8))
+    }
+}
+
+impl PixelFormat for Rgb<u8> {
+    const MAX: Option<f32> = Some(255.0);
+
+    fn encode(pixel: PremulPixel, color_space: ColorSpace) -> [f32; 4] {
+        let p = color_space.encode(PremulPixel { a: 1.0, ..pixel }.unpremultiply());
+        [p.r, p.g, p.b, 1.0]
+    }
+
+    fn from_encoded([r, g, b, _]: [f32; 4]) -> Self {
+        Rgb([r, g, b].map(|v| quantize(v, 255.0) as u8))
+    }
+}
+
+impl PixelFormat for Luma<u8> {
+    const MAX: Option<f32> = Some(255.0);
+
+    fn encode(pixel: PremulPixel, color_space: ColorSpace) -> [f32; 4] {
+        // Rec. 709 luminance, taken in linear light before encoding.
+        let y = 0.2126 * pixel.r + 0.7152 * pixel.g + 0.0722 * pixel.b;
+        let p = color_space.encode(
+            PremulPixel {
+                r: y,
+                g: y,
+                b: y,
+                a: 1.0,
+            }
+            .unpremultiply(),
+        );
+        [p.r, 0.0, 0.0, 1.0]
+    }
+
+    fn from_encoded([y, ..]: [f32; 4]) -> Self {
+        Luma([quantize(y, 255.0) as u8])
+    }
+}
+
+impl PixelFormat for Rgba<u16> {
+    const MAX: Option<f32> = Some(65535.0);
+
+    fn encode(pixel: PremulPixel, color_space: ColorSpace) -> [f32; 4] {
+        let p = color_space.encode(pixel.unpremultiply());
+        [p.r, p.g, p.b, p.a]
+    }
+
+    fn from_encoded(channels: [f32; 4]) -> Self {
+        Rgba(channels.map(|v| quantize(v, 65535.0) as u16))
+    }
+}
+
+impl PixelFormat for Rgba<f32> {
+    const MAX: Option<f32> = None;
+
+    fn encode(pixel: PremulPixel, color_space: ColorSpace) -> [f32; 4] {
+        let p = color_space.encode(pixel.unpremultiply());
+        [p.r, p.g, p.b, p.a]
+    }
+
+    fn from_encoded(channels: [f32; 4]) -> Self {
+        Rgba(channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::ImageBuffer;
+
+    use super::*;
+    use crate::{Image, Pixel, RenderOptions, Uniform};
+
+    const HALF_WHITE: PremulPixel = PremulPixel {
+        r: 0.5,
+        g: 0.5,
+        b: 0.5,
+        a: 0.5,
+    };
+
+    #[test]
+    fn integer_channels_round_to_the_nearest_level() {
+        let Rgba(rgba) = Rgba::<u8>::from_encoded([0.999, 0.5, 0.001, 0.502]);
+        assert_eq!(rgba, [255, 128, 0, 128]);
+        let Rgba(rgba) = Rgba::<u16>::from_encoded([0.999_995, 0.5, 0.000_001, 0.25]);
+        assert_eq!(rgba, [65535, 32768, 0, 16384]);
+        assert_eq!(
+            Luma::<u8>::from_encoded([0.998, 0.0, 0.0, 1.0]),
+            Luma([254])
+        );
+    }
+
+    #[test]
+    fn integer_channels_clamp_out_of_range_values() {
+        let Rgba(rgba) = Rgba::<u8>::from_encoded([-0.5, 1.7, f32::INFINITY, -0.0]);
+        assert_eq!(rgba, [0, 255, 255, 0]);
+        let Rgba(rgba) = Rgba::<u16>::from_encoded([2.0, -1.0, 1.0, 0.0]);
+        assert_eq!(rgba, [65535, 0, 65535, 0]);
+        assert_eq!(
+            Rgb::<u8>::from_encoded([3.0, -3.0, 0.5, 0.0]),
+            Rgb([255, 0, 128])
+        );
+    }
+
+    #[test]
+    fn float_channels_keep_out_of_range_values() {
+        let channels = [1.5, -0.25, 4.0, 1.0];
+        assert_eq!(Rgba::<f32>::from_encoded(channels), Rgba(channels));
+        let bright = PremulPixel {
+            r: 3.0,
+            g: 1.0,
+            b: 0.5,
+            a: 1.0,
+        };
+        assert_eq!(
+            Rgba::<f32>::encode(bright, ColorSpace::Linear),
+            [3.0, 1.0, 0.5, 1.0]
+        );
+    }
+
+    #[test]
+    fn formats_without_alpha_composite_over_black() {
+        assert_eq!(
+            Rgb::<u8>::encode(HALF_WHITE, ColorSpace::Linear),
+            [0.5, 0.5, 0.5, 1.0]
+        );
+        assert_eq!(Luma::<u8>::encode(HALF_WHITE, ColorSpace::Linear)[0], 0.5);
+        let [r, ..] = Rgb::<u8>::encode(HALF_WHITE, ColorSpace::Srgb);
+        assert!((r - 0.7354).abs() < 1e-3, "{r}");
+        assert_eq!(
+            Rgba::<u8>::encode(HALF_WHITE, ColorSpace::Linear),
+            [1.0, 1.0, 1.0, 0.5]
+        );
+    }
+
+    #[test]
+    fn render_into_fills_a_16_bit_buffer() {
+        let image = Uniform::new(Pixel {
+            r: 0.25,
+            g: 0.5,
+            b: 1.0,
+            a: 1.0,
+        });
+        let options = RenderOptions {
+            color_space: ColorSpace::Linear,
+            ..RenderOptions::default()
+        };
+        let mut buf = ImageBuffer::<Rgba<u16>, _>::new(3, 2);
+        image.render_into(&mut buf, &options);
+        assert!(buf.pixels().all(|p| p.0 == [16384, 32768, 65535, 65535]));
+
+        let mut srgb = ImageBuffer::<Rgba<u16>, _>::new(1, 1);
+        image.render_into(&mut srgb, &RenderOptions::default());
+        let narrow = image.render(1, 1);
+        for (wide, narrow) in srgb.get_pixel(0, 0).0.iter().zip(narrow) {
+            assert_eq!((*wide as f32 / 257.0).round() as u8, narrow);
+        }
+    }
+}
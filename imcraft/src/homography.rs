@@ -0,0 +1,243 @@
+use crate::Mat3;
+
+/// Twice the area of a triangle of normalised points below which they count
+/// as collinear.
+const COLLINEAR_TOLERANCE: f64 = 1e-6;
+
+/// How small a pivot may get, relative to the largest entry of the system,
+/// before the normal equations count as rank deficient.
+const PIVOT_TOLERANCE: f64 = 1e-10;
+
+/// Computes the projective matrix that maps each `src` corner onto the matching
+/// `dst` corner, or `None` if three of the points are collinear.
+pub fn homography(src: [(f32, f32); 4], dst: [(f32, f32); 4]) -> Option<Mat3> {
+    if has_collinear_triple(&src) || has_collinear_triple(&dst) {
+        return None;
+    }
+    homography_least_squares(&src, &dst)
+}
+
+/// Whether any three of the points lie on a line, judged after normalising
+/// them so the answer does not depend on their position or scale.
+fn has_collinear_triple(points: &[(f32, f32)]) -> bool {
+    let norm = Normalization::new(points.iter().copied());
+    let p: Vec<_> = points.iter().map(|&p| norm.apply(p)).collect();
+    let n = p.len();
+    (0..n).any(|i| {
+        (i + 1..n).any(|j| {
+            (j + 1..n).any(|k| {
+                let cross =
+                    (p[j].0 - p[i].0) * (p[k].1 - p[i].1) - (p[j].1 - p[i].1) * (p[k].0 - p[i].0);
+                cross.abs() < COLLINEAR_TOLERANCE
+            })
+        })
+    })
+}
+
+/// Fits the projective matrix that best maps each `src` point onto the `dst`
+/// point at the same index, in the least-squares sense. Needs at least four pairs.
+pub fn homography_least_squares(src: &[(f32, f32)], dst: &[(f32, f32)]) -> Option<Mat3> {
+    if src.len() < 4 || src.len() != dst.len() {
+        return None;
+    }
+    let pairs = src.iter().copied().zip(dst.iter().copied());
+
+    // Normalising both point sets keeps the normal equations well conditioned
+    // when coordinates are in the hundreds or thousands (Hartley, 1997).
+    let src_norm = Normalization::new(src.iter().copied());
+    let dst_norm = Normalization::new(dst.iter().copied());
+
+    // With h33 fixed to 1, each pair contributes two rows to A·h = b.
+    let mut ata = [[0.0; 8]; 8];
+    let mut atb = [0.0; 8];
+    for (s, d) in pairs {
+        let (x, y) = src_norm.apply(s);
+        let (u, v) = dst_norm.apply(d);
+        let rows = [
+            ([x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y], u),
+            ([0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y], v),
+        ];
+        for (row, rhs) in rows {
+            for i in 0..8 {
+                for j in 0..8 {
+                    ata[i][j] += row[i] * row[j];
+                }
+                atb[i] += row[i] * rhs;
+            }
+        }
+    }
+
+    let h = solve(ata, atb)?;
+    let normalized = [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], 1.0]];
+    let m = multiply(dst_norm.inverse(), multiply(normalized, src_norm.matrix()));
+    if m[2][2].abs() < f64::EPSILON {
+        return None;
+    }
+    let m = Mat3(m.map(|row| row.map(|v| (v / m[2][2]) as f32)));
+    if m.is_singular() || m.0.iter().flatten().any(|v| !v.is_finite()) {
+        return None;
+    }
+    Some(m)
+}
+
+/// A similarity transform moving the centroid to the origin and the mean
+/// distance from it to √2.
+struct Normalization {
+    cx: f64,
+    cy: f64,
+    scale: f64,
+}
+
+impl Normalization {
+    fn new(points: impl Iterator<Item = (f32, f32)> + Clone) -> Self {
+        let n = points.clone().count() as f64;
+        let (sx, sy) = points.clone().fold((0.0, 0.0), |(sx, sy), (x, y)| {
+            (sx + x as f64, sy + y as f64)
+        });
+        let (cx, cy) = (sx / n, sy / n);
+        let mean = points
+            .map(|(x, y)| (x as f64 - cx).hypot(y as f64 - cy))
+            .sum::<f64>()
+            / n;
+        let scale = if mean > 0.0 {
+            std::f64::consts::SQRT_2 / mean
+        } else {
+            1.0
+        };
+        Normalization { cx, cy, scale }
+    }
+
+    fn apply(&self, (x, y): (f32, f32)) -> (f64, f64) {
+        (
+            (x as f64 - self.cx) * self.scale,
+            (y as f64 - self.cy) * self.scale,
+        )
+    }
+
+    fn matrix(&self) -> [[f64; 3]; 3] {
+        [
+            [self.scale, 0.0, -self.cx * self.scale],
+            [0.0, self.scale, -self.cy * self.scale],
+            [0.0, 0.0, 1.0],
+        ]
+    }
+
+    fn inverse(&self) -> [[f64; 3]; 3] {
+        [
+            [1.0 / self.scale, 0.0, self.cx],
+            [0.0, 1.0 / self.scale, self.cy],
+            [0.0, 0.0, 1.0],
+        ]
+    }
+}
+
+fn multiply(a: [[f64; 3]; 3], b: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            out[i][j] = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+/// Gaussian elimination with partial pivoting, or `None` if `a` is (nearly)
+/// singular.
+fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    let largest = a.iter().flatten().fold(0.0f64, |m, v| m.max(v.abs()));
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() <= largest * PIVOT_TOLERANCE {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..N {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col];
+            for (value, pivot) in a[row].iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let rest: f64 = (row + 1..N).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [(f32, f32); 4] = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+
+    fn assert_maps(m: &Mat3, src: &[(f32, f32)], dst: &[(f32, f32)]) {
+        for (&(x, y), &(u, v)) in src.iter().zip(dst) {
+            let (x2, y2) = m.apply(x, y).unwrap();
+            assert!(
+                (x2 - u).abs() < 1e-3 && (y2 - v).abs() < 1e-3,
+                "({x}, {y}) mapped to ({x2}, {y2}), expected ({u}, {v})"
+            );
+        }
+    }
+
+    #[test]
+    fn affine_quad_gives_the_affine_matrix() {
+        let dst = [(10.0, 20.0), (14.0, 20.0), (14.0, 22.0), (10.0, 22.0)];
+        let m = homography(SQUARE, dst).unwrap();
+        let expected = Mat3::translate(10.0, 20.0) * Mat3::scale(4.0, 2.0);
+        for (row, expected) in m.0.iter().zip(expected.0) {
+            for (v, e) in row.iter().zip(expected) {
+                assert!((v - e).abs() < 1e-4, "{m:?} != {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn perspective_quad_maps_corners_exactly() {
+        let src = [(0.0, 0.0), (512.0, 0.0), (512.0, 512.0), (0.0, 512.0)];
+        let dst = [
+            (100.0, 100.0),
+            (400.0, 120.0),
+            (380.0, 400.0),
+            (90.0, 350.0),
+        ];
+        let m = homography(src, dst).unwrap();
+        assert_maps(&m, &src, &dst);
+        assert_ne!(m.0[2], [0.0, 0.0, 1.0], "should be projective");
+    }
+
+    #[test]
+    fn least_squares_recovers_a_known_matrix() {
+        let truth = Mat3([[1.2, 0.1, 30.0], [-0.2, 0.9, 15.0], [0.0004, -0.0002, 1.0]]);
+        let src: Vec<_> = (0..5)
+            .flat_map(|i| (0..4).map(move |j| (i as f32 * 90.0, j as f32 * 110.0)))
+            .collect();
+        let dst: Vec<_> = src
+            .iter()
+            .map(|&(x, y)| truth.apply(x, y).unwrap())
+            .collect();
+        let m = homography_least_squares(&src, &dst).unwrap();
+        assert_maps(&m, &src, &dst);
+    }
+
+    #[test]
+    fn degenerate_inputs_are_rejected() {
+        let line = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (0.0, 1.0)];
+        assert_eq!(homography(SQUARE, line), None);
+        assert_eq!(homography(line, SQUARE), None);
+
+        let collinear: Vec<_> = (0..6).map(|i| (i as f32, 2.0 * i as f32)).collect();
+        let spread: Vec<_> = (0..6).map(|i| (i as f32, (i * i) as f32)).collect();
+        assert_eq!(homography_least_squares(&collinear, &spread), None);
+        assert_eq!(homography_least_squares(&spread, &collinear), None);
+
+        assert_eq!(homography_least_squares(&SQUARE[..3], &SQUARE[..3]), None);
+        assert_eq!(homography_least_squares(&SQUARE, &SQUARE[..3]), None);
+    }
+}
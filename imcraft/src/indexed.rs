@@ -0,0 +1,166 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use image::ImageFormat;
+
+use crate::{Error, Palette};
+
+/// Writes `indices` into `palette` as an indexed PNG or GIF, chosen by the
+/// extension of `path`.
+pub(crate) fn write_indexed(
+    path: &Path,
+    indices: &[u8],
+    width: usize,
+    height: usize,
+    palette: &Palette,
+) -> Result<(), Error> {
+    let size = Error::check_dimensions(width, height)?;
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Png) => write_png(path, indices, size, palette),
+        Ok(ImageFormat::Gif) => {
+            let invalid = || Error::InvalidDimensions { width, height };
+            let w = u16::try_from(width).map_err(|_| invalid())?;
+            let h = u16::try_from(height).map_err(|_| invalid())?;
+            write_gif(path, indices, (w, h), palette)
+        }
+        _ => Err(Error::unsupported_output(path)),
+    }
+}
+
+fn write_png(
+    path: &Path,
+    indices: &[u8],
+    (width, height): (u32, u32),
+    palette: &Palette,
+) -> Result<(), Error> {
+    let colors = palette.colors();
+    let depth = match colors.len() {
+        0..=2 => png::BitDepth::One,
+        3..=4 => png::BitDepth::Two,
+        5..=16 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+
+    // Pack indices most significant bits first, each row starting on a byte.
+    let bits = depth as usize;
+    let row_bytes = (width as usize * bits).div_ceil(8);
+    let mut data = vec![0; row_bytes * height as usize];
+    for (src, dst) in indices
+        .chunks_exact(width as usize)
+        .zip(data.chunks_exact_mut(row_bytes))
+    {
+        for (x, &i) in src.iter().enumerate() {
+            let bit = x * bits;
+            dst[bit / 8] |= i << (8 - bits - bit % 8);
+        }
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_palette(
+        colors
+            .iter()
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect::<Vec<_>>(),
+    );
+    if let Some(last) = colors.iter().rposition(|c| c[3] < 255) {
+        encoder.set_trns(colors[..=last].iter().map(|c| c[3]).collect::<Vec<_>>());
+    }
+    let mut writer = encoder.write_header().map_err(Error::png)?;
+    writer.write_image_data(&data).map_err(Error::png)?;
+    writer.finish().map_err(Error::png)
+}
+
+/// GIF has no partial transparency: the most transparent colour under half
+/// alpha becomes the transparent one and alpha is otherwise ignored.
+fn write_gif(
+    path: &Path,
+    indices: &[u8],
+    (width, height): (u16, u16),
+    palette: &Palette,
+) -> Result<(), Error> {
+    let colors = palette.colors();
+    let rgb: Vec<u8> = colors.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, width, height, &rgb).map_err(Error::gif)?;
+    let frame = gif::Frame {
+        width,
+        height,
+        buffer: Cow::Borrowed(indices),
+        transparent: (0..colors.len())
+            .filter(|&i| colors[i][3] < 128)
+            .min_by_key(|&i| colors[i][3])
+            .map(|i| i as u8),
+        ..gif::Frame::default()
+    };
+    encoder.write_frame(&frame).map_err(Error::gif)?;
+    encoder.into_inner()?.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(extension: &str, colors: Vec<[u8; 4]>) {
+        let (width, height) = (13, 5);
+        let palette = Palette::new(colors);
+        let n = palette.colors().len();
+        let indices: Vec<u8> = (0..width * height).map(|i| (i * 7 % n) as u8).collect();
+        let path = std::env::temp_dir().join(format!(
+            "imcraft-indexed-{}-{n}.{extension}",
+            std::process::id()
+        ));
+        write_indexed(&path, &indices, width, height, &palette).unwrap();
+        let decoded = image::open(&path).map(|image| image.into_rgba8());
+        std::fs::remove_file(&path).unwrap();
+        let decoded = decoded.unwrap();
+
+        assert_eq!(decoded.dimensions(), (width as u32, height as u32));
+        for (pixel, &i) in decoded.pixels().zip(&indices) {
+            let expected = palette.colors()[i as usize];
+            if expected[3] == 0 {
+                assert_eq!(pixel.0[3], 0, "{extension} with {n} colours");
+            } else {
+                assert_eq!(pixel.0, expected, "{extension} with {n} colours");
+            }
+        }
+    }
+
+    fn colors(n: usize) -> Vec<[u8; 4]> {
+        (0..n)
+            .map(|i| match i {
+                0 => [0; 4],
+                _ => [(i * 37) as u8, (i * 91) as u8, (255 - i) as u8, 255],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn indexed_png_round_trips_at_every_bit_depth() {
+        for n in [1, 2, 3, 4, 16, 17, 256] {
+            round_trip("png", colors(n));
+        }
+        round_trip("png", vec![[200, 100, 50, 128], [1, 2, 3, 255]]);
+    }
+
+    #[test]
+    fn indexed_gif_round_trips() {
+        for n in [2, 5, 256] {
+            round_trip("gif", colors(n));
+        }
+    }
+
+    #[test]
+    fn other_extensions_are_rejected() {
+        let palette = Palette::new(colors(2));
+        let path = std::env::temp_dir().join("imcraft-indexed.jpg");
+        let result = write_indexed(&path, &[0; 4], 2, 2, &palette);
+        assert!(matches!(result, Err(Error::UnsupportedFormat(_))));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,180 @@
+use std::marker::PhantomData;
+
+use crate::composite::composite;
+use crate::{BlendMode, ColorSpace, Image, Operator, Pixel, PremulPixel, Rect};
+
+/// One entry of a [`Layers`] stack. `I` is the trait object images are boxed
+/// as; [`Layer::new_sync`] boxes them as `dyn Image + Send + Sync` so the
+/// stack can be rendered in parallel.
+pub struct Layer<'a, I: Image + ?Sized + 'a = dyn Image + 'a> {
+    image: Box<I>,
+    pub mode: BlendMode,
+    pub opacity: f32,
+    pub visible: bool,
+    /// The encoding the blend formulas see, as in
+    /// [`Composite::with_blend_space`](crate::Composite::with_blend_space).
+    pub blend_space: ColorSpace,
+    lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a> Layer<'a> {
+    pub fn new(image: impl Image + 'a) -> Self {
+        Self::from_box(Box::new(image))
+    }
+}
+
+impl<'a> Layer<'a, dyn Image + Send + Sync + 'a> {
+    pub fn new_sync(image: impl Image + Send + Sync + 'a) -> Self {
+        Self::from_box(Box::new(image))
+    }
+}
+
+impl<'a, I: Image + ?Sized + 'a> Layer<'a, I> {
+    pub fn from_box(image: Box<I>) -> Self {
+        Self {
+            image,
+            mode: BlendMode::Normal,
+            opacity: 1.0,
+            visible: true,
+            blend_space: ColorSpace::Srgb,
+            lifetime: PhantomData,
+        }
+    }
+
+    pub fn with_blend(mut self, mode: BlendMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    pub fn with_blend_space(mut self, space: ColorSpace) -> Self {
+        self.blend_space = space;
+        self
+    }
+}
+
+/// A stack of layers drawn bottom to top, each over everything below it.
+/// Unlike nested `join`s, the stack can be built and edited at runtime.
+pub struct Layers<'a, I: Image + ?Sized + 'a = dyn Image + 'a> {
+    layers: Vec<Layer<'a, I>>,
+}
+
+impl<'a, I: Image + ?Sized + 'a> Default for Layers<'a, I> {
+    fn default() -> Self {
+        Self { layers: Vec::new() }
+    }
+}
+
+impl<'a, I: Image + ?Sized + 'a> Layers<'a, I> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, layer: Layer<'a, I>) {
+        self.layers.push(layer);
+    }
+
+    pub fn layers(&self) -> &[Layer<'a, I>] {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut Vec<Layer<'a, I>> {
+        &mut self.layers
+    }
+}
+
+impl<'a, I: Image + ?Sized + 'a> FromIterator<Layer<'a, I>> for Layers<'a, I> {
+    fn from_iter<T: IntoIterator<Item = Layer<'a, I>>>(iter: T) -> Self {
+        Self {
+            layers: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<I: Image + ?Sized> Layers<'_, I> {
+    /// Unions `rect` over the layers that can be seen.
+    fn union(&self, rect: impl Fn(&I) -> Option<Rect>) -> Option<Rect> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.visible && layer.opacity > 0.0)
+            .try_fold(Rect::default(), |acc, layer| {
+                Some(acc.union(&rect(&layer.image)?))
+            })
+    }
+}
+
+impl<I: Image + ?Sized> Image for Layers<'_, I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        let mut acc = PremulPixel::TRANSPARENT;
+        for layer in &self.layers {
+            let opacity = layer.opacity.clamp(0.0, 1.0);
+            if !layer.visible || opacity == 0.0 {
+                continue;
+            }
+            let px = layer.image.sample(x, y, footprint) * opacity;
+            acc = composite(acc, px, Operator::SrcOver, layer.mode, layer.blend_space);
+        }
+        acc
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        self.union(|image| image.bounds())
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        self.union(|image| image.extent())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct Fill(Pixel);
+
+    impl Image for Fill {
+        fn get(&self, _x: f32, _y: f32) -> Pixel {
+            self.0
+        }
+    }
+
+    #[test]
+    fn layers_accept_shared_single_threaded_images() {
+        let red = Rc::new(Fill(Pixel::from_srgb(1.0, 0.0, 0.0, 1.0)));
+        let stack: Layers = [
+            Layer::new(Rc::clone(&red)),
+            Layer::new(red).with_visible(false),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(stack.render(1, 1), [255, 0, 0, 255]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn sync_layers_render_in_parallel() {
+        let mut stack = Layers::new();
+        stack.push(Layer::new_sync(Fill(Pixel::from_srgb(0.2, 0.4, 0.6, 1.0))));
+        stack.push(
+            Layer::new_sync(Fill(Pixel::from_srgb(1.0, 0.5, 0.0, 0.5)))
+                .with_blend(BlendMode::Multiply)
+                .with_opacity(0.75),
+        );
+        assert_eq!(stack.par_render(7, 5), stack.render(7, 5));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::rect;
+use crate::{EdgeMode, Image, Pixel, PremulPixel, Rect};
+
+/// Where content sits on a canvas that is larger or smaller than it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// How much of the spare room goes before the content on each axis.
+    pub(crate) fn factors(self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// Samples `image` inside `rect` and fills the outside as `edge` says. Clamping
+/// repeats the content half a unit in from each edge, i.e. the centres of the
+/// outermost pixels of a pixel-aligned rectangle.
+fn sample_within<I: Image>(
+    image: &I,
+    rect: Rect,
+    edge: EdgeMode,
+    x: f32,
+    y: f32,
+    footprint: f32,
+) -> PremulPixel {
+    if rect.contains(x, y) {
+        return image.sample(x, y, footprint);
+    }
+    if rect.is_empty() {
+        return PremulPixel::TRANSPARENT;
+    }
+    let wrap = |v: f32, start: f32, len: f32| {
+        let t = v - start;
+        let t = match edge {
+            EdgeMode::Clamp => t.clamp(0.5f32.min(len / 2.0), (len - 0.5).max(len / 2.0)),
+            EdgeMode::Repeat => t.rem_euclid(len),
+            EdgeMode::Mirror => {
+                let t = t.rem_euclid(2.0 * len);
+                if t < len {
+                    t
+                } else {
+                    2.0 * len - t
+                }
+            }
+            EdgeMode::Transparent | EdgeMode::Border(_) => t,
+        };
+        // Reflecting `len` itself, or rounding in `rem_euclid`, can land on
+        // the far edge, which is just outside the rectangle.
+        (start + t).min((start + len).next_down())
+    };
+    match edge {
+        EdgeMode::Transparent => PremulPixel::TRANSPARENT,
+        EdgeMode::Border(pixel) => pixel.premultiply(),
+        _ => image.sample(
+            wrap(x, rect.x, rect.width),
+            wrap(y, rect.y, rect.height),
+            footprint,
+        ),
+    }
+}
+
+pub struct Crop<I> {
+    image: I,
+    rect: Rect,
+    edge: EdgeMode,
+}
+
+impl<I: Image> Crop<I> {
+    pub(crate) fn new(image: I, rect: Rect) -> Self {
+        Self {
+            image,
+            rect,
+            edge: EdgeMode::Transparent,
+        }
+    }
+
+    /// Sets what replaces the image outside the window.
+    pub fn with_edge(mut self, edge: EdgeMode) -> Self {
+        self.edge = edge;
+        self
+    }
+}
+
+impl<I: Image> Image for Crop<I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        sample_within(&self.image, self.rect, self.edge, x, y, footprint)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        if !self.edge.is_transparent() {
+            return None;
+        }
+        rect::intersect(self.image.bounds(), Some(self.rect))
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        if !self.edge.is_transparent() {
+            return None;
+        }
+        rect::intersect(self.image.extent(), Some(self.rect))
+    }
+}
+
+pub struct Pad<I> {
+    image: I,
+    /// The bounds of `image`; `None` leaves an unbounded image untouched.
+    content: Option<Rect>,
+    rect: Rect,
+    /// The left, top, right and bottom margins.
+    margins: [f32; 4],
+    edge: EdgeMode,
+}
+
+impl<I: Image> Pad<I> {
+    pub(crate) fn new(image: I, left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        let margins = [left, top, right, bottom];
+        let content = image.bounds();
+        let rect = content.map_or(Rect::default(), |c| grow(c, margins));
+        Self {
+            image,
+            content,
+            rect,
+            margins,
+            edge: EdgeMode::Transparent,
+        }
+    }
+
+    /// Sets how the margin is filled from the content.
+    pub fn with_edge(mut self, edge: EdgeMode) -> Self {
+        self.edge = edge;
+        self
+    }
+}
+
+/// Adds `margins` around `rect`, unless it is empty: nothing has no edges to
+/// pad out from.
+fn grow(rect: Rect, [left, top, right, bottom]: [f32; 4]) -> Rect {
+    if rect.is_empty() {
+        return rect;
+    }
+    Rect::new(
+        rect.x - left,
+        rect.y - top,
+        rect.width + left + right,
+        rect.height + top + bottom,
+    )
+}
+
+impl<I: Image> Image for Pad<I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        let Some(content) = self.content else {
+            return self.image.sample(x, y, footprint);
+        };
+        if !self.rect.contains(x, y) {
+            return PremulPixel::TRANSPARENT;
+        }
+        sample_within(&self.image, content, self.edge, x, y, footprint)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        self.content.map(|_| self.rect)
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        self.content?;
+        Some(grow(self.image.extent()?, self.margins))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufImage, Filter};
+
+    fn opaque(width: usize, height: usize) -> BufImage {
+        BufImage::from_rgba8(width, height, vec![255; width * height * 4])
+    }
+
+    /// The opaque pixels of a render, as `(x, y, width, height)`.
+    fn opaque_area(rgba: &[u8], width: usize) -> (usize, usize, usize, usize) {
+        let opaque: Vec<_> = rgba
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, p)| p[3] == 255)
+            .map(|(i, _)| (i % width, i / width))
+            .collect();
+        assert!(rgba.chunks_exact(4).all(|p| p[3] == 0 || p[3] == 255));
+        let x = opaque.iter().map(|p| p.0).min().unwrap();
+        let y = opaque.iter().map(|p| p.1).min().unwrap();
+        let w = opaque.iter().map(|p| p.0).max().unwrap() + 1 - x;
+        let h = opaque.iter().map(|p| p.1).max().unwrap() + 1 - y;
+        assert_eq!(opaque.len(), w * h);
+        (x, y, w, h)
+    }
+
+    #[test]
+    fn canvas_places_filtered_content_at_each_anchor() {
+        let anchors = [
+            (Anchor::TopLeft, 0, 0),
+            (Anchor::Top, 2, 0),
+            (Anchor::TopRight, 4, 0),
+            (Anchor::Left, 0, 2),
+            (Anchor::Center, 2, 2),
+            (Anchor::Right, 4, 2),
+            (Anchor::BottomLeft, 0, 4),
+            (Anchor::Bottom, 2, 4),
+            (Anchor::BottomRight, 4, 4),
+        ];
+        for filter in [
+            Filter::Nearest,
+            Filter::Bilinear,
+            Filter::Bicubic,
+            Filter::Lanczos3,
+        ] {
+            let image = opaque(4, 4).with_filter(filter);
+            for (anchor, x, y) in anchors {
+                let rendered = (&image).canvas(8, 8, anchor).render(8, 8);
+                assert_eq!(
+                    opaque_area(&rendered, 8),
+                    (x, y, 4, 4),
+                    "{filter:?} at {anchor:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn canvas_rounds_half_offsets_the_same_way() {
+        let image = opaque(4, 4).with_filter(Filter::Bilinear);
+        let grown = (&image).canvas(7, 7, Anchor::Center).render(7, 7);
+        assert_eq!(opaque_area(&grown, 7), (2, 2, 4, 4));
+        let shrunk = (&image).canvas(3, 3, Anchor::Center).render(3, 3);
+        assert_eq!(opaque_area(&shrunk, 3), (0, 0, 3, 3));
+        assert_eq!(
+            (&image).canvas(4, 4, Anchor::BottomRight).render(4, 4),
+            image.render(4, 4)
+        );
+    }
+
+    #[test]
+    fn wrapped_edges_stay_inside_the_rectangle() {
+        let window = Rect::new(0.0, 0.0, 4.0, 4.0);
+        for edge in [EdgeMode::Clamp, EdgeMode::Repeat, EdgeMode::Mirror] {
+            let crop = opaque(4, 4).crop(window).with_edge(edge);
+            for x in [4.0, 8.0, 12.0, -4.0, -1e-7, 4.0f32.next_up()] {
+                assert_eq!(crop.get(x, 1.5).a, 1.0, "{edge:?} at {x}");
+            }
+        }
+    }
+
+    #[test]
+    fn pad_grows_bounded_content_only() {
+        let padded = opaque(4, 4)
+            .with_filter(Filter::Bilinear)
+            .pad(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(padded.extent(), Some(Rect::new(-1.0, -2.0, 8.0, 10.0)));
+        assert_eq!(padded.bounds(), Some(Rect::new(-1.5, -2.5, 9.0, 11.0)));
+
+        let empty = crate::Uniform::new(Pixel::TRANSPARENT).pad(1.0, 1.0, 1.0, 1.0);
+        assert!(empty.bounds().unwrap().is_empty());
+        assert_eq!(empty.get(0.0, 0.0).a, 0.0);
+
+        let unbounded = opaque(4, 4).with_edge(EdgeMode::Repeat);
+        let padded = (&unbounded).pad(1.0, 1.0, 1.0, 1.0);
+        assert_eq!(padded.bounds(), None);
+        assert_eq!(padded.render(6, 6), unbounded.render(6, 6));
+    }
+}
@@ -1,6 +1,44 @@
+use std::ops::{Add, AddAssign, DerefMut, Mul, Range};
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
 
-#[derive(Clone, Copy)]
+use image::{ImageBuffer, Rgba};
+
+mod color;
+mod composite;
+mod dither;
+mod error;
+mod format;
+mod homography;
+mod indexed;
+mod layers;
+mod layout;
+mod mask;
+mod matrix;
+mod palette;
+mod rect;
+mod render;
+mod sampling;
+mod stream;
+
+pub use color::ColorSpace;
+pub use composite::{BlendMode, Composite, Operator};
+pub use dither::Dither;
+pub use error::Error;
+pub use format::PixelFormat;
+pub use homography::{homography, homography_least_squares};
+pub use layers::{Layer, Layers};
+pub use layout::{Anchor, Crop, Pad};
+pub use mask::{Mask, MaskMode, Opacity};
+pub use matrix::{Mat3, SingularMatrix};
+pub use palette::{Palette, PaletteMethod};
+pub use rect::Rect;
+pub use render::{Reconstruction, RenderOptions, Samples};
+pub use sampling::{EdgeMode, Filter, Mipmap, Resample};
+
+/// A straight-alpha colour in linear light.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Pixel {
     pub r: f32,
     pub g: f32,
@@ -8,19 +46,165 @@ pub struct Pixel {
     pub a: f32,
 }
 
+impl Pixel {
+    pub const TRANSPARENT: Pixel = Pixel {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.0,
+    };
+
+    /// Builds a pixel from sRGB-encoded components, such as a CSS colour.
+    pub fn from_srgb(r: f32, g: f32, b: f32, a: f32) -> Pixel {
+        ColorSpace::Srgb.decode(Pixel { r, g, b, a })
+    }
+
+    pub fn premultiply(self) -> PremulPixel {
+        PremulPixel {
+            r: self.r * self.a,
+            g: self.g * self.a,
+            b: self.b * self.a,
+            a: self.a,
+        }
+    }
+}
+
+/// A colour whose `r`, `g` and `b` are already scaled by `a`. Filtering and
+/// compositing work on these, so edges never have to be divided back out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PremulPixel {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl PremulPixel {
+    pub const TRANSPARENT: PremulPixel = PremulPixel {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.0,
+    };
+
+    pub fn unpremultiply(self) -> Pixel {
+        if self.a == 0.0 {
+            return Pixel::TRANSPARENT;
+        }
+        Pixel {
+            r: self.r / self.a,
+            g: self.g / self.a,
+            b: self.b / self.a,
+            a: self.a,
+        }
+    }
+}
+
+impl From<Pixel> for PremulPixel {
+    fn from(pixel: Pixel) -> Self {
+        pixel.premultiply()
+    }
+}
+
+impl From<PremulPixel> for Pixel {
+    fn from(pixel: PremulPixel) -> Self {
+        pixel.unpremultiply()
+    }
+}
+
+impl Add for PremulPixel {
+    type Output = PremulPixel;
+
+    fn add(self, rhs: PremulPixel) -> PremulPixel {
+        PremulPixel {
+            r: self.r + rhs.r,
+            g: self.g + rhs.g,
+            b: self.b + rhs.b,
+            a: self.a + rhs.a,
+        }
+    }
+}
+
+impl AddAssign for PremulPixel {
+    fn add_assign(&mut self, rhs: PremulPixel) {
+        *self = *self + rhs;
+    }
+}
+
+impl Mul<f32> for PremulPixel {
+    type Output = PremulPixel;
+
+    fn mul(self, rhs: f32) -> PremulPixel {
+        PremulPixel {
+            r: self.r * rhs,
+            g: self.g * rhs,
+            b: self.b * rhs,
+            a: self.a * rhs,
+        }
+    }
+}
+
+/// An infinite image plane. Pixel `(x, y)` of a rendered raster covers the unit
+/// square `[x, x + 1) × [y, y + 1)` and is sampled at its centre.
+///
+/// The trait is dyn-compatible: everything that consumes `self` or takes generic
+/// arguments requires `Self: Sized`, so graphs can be assembled at runtime from
+/// `Box<dyn Image>` or `Arc<dyn Image + Send + Sync>`, which are images themselves.
 pub trait Image {
     fn get(&self, x: f32, y: f32) -> Pixel;
 
-    fn transform(self, matrix: [[f32; 3]; 3]) -> impl Image + Sized
+    /// Samples the image at `(x, y)` where one output pixel covers roughly
+    /// `footprint` units of this image's plane, so minifying sources can prefilter.
+    /// This is what adaptors and rendering call; `get` is its straight-alpha view.
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        let _ = footprint;
+        self.get(x, y).premultiply()
+    }
+
+    /// Where the image can be visible, or `None` if it may cover the whole
+    /// plane. Bounds are conservative, though filters minifying through mip
+    /// levels can blur a little past them.
+    fn bounds(&self) -> Option<Rect> {
+        None
+    }
+
+    /// The rectangle the content itself occupies, which [`Image::canvas`]
+    /// anchors on. Unlike [`Image::bounds`] it leaves out the fringe a filter
+    /// blurs past the outermost texels; by default the two are the same.
+    fn extent(&self) -> Option<Rect> {
+        self.bounds()
+    }
+
+    /// Maps the image through `matrix`. A singular matrix leaves nothing
+    /// visible; use [`Image::try_transform`] to detect that case.
+    fn transform(self, matrix: impl Into<Mat3>) -> Transform<Self>
     where
         Self: Sized,
     {
         Transform {
             image: self,
-            matrix: invert(matrix),
+            matrix: matrix
+                .into()
+                .normalize_sign()
+                .invert()
+                .unwrap_or(Mat3::ZERO),
         }
     }
 
+    fn try_transform(self, matrix: impl Into<Mat3>) -> Result<Transform<Self>, SingularMatrix>
+    where
+        Self: Sized,
+    {
+        Ok(Transform {
+            image: self,
+            matrix: matrix
+                .into()
+                .normalize_sign()
+                .invert()
+                .ok_or(SingularMatrix)?,
+        })
+    }
+
     fn join(self, other: impl Image) -> impl Image + Sized
     where
         Self: Sized,
@@ -31,53 +215,352 @@ pub trait Image {
         }
     }
 
-    fn translate(self, x: f32, y: f32) -> impl Image + Sized
+    /// Combines `other` (the source) with this image (the destination) using a
+    /// Porter-Duff operator. `join` is the same as `Operator::SrcOver`.
+    fn composite<I: Image>(self, other: I, op: Operator) -> Composite<Self, I>
+    where
+        Self: Sized,
+    {
+        Composite::new(self, other, op, BlendMode::Normal)
+    }
+
+    /// Draws `other` over this image, mixing colours where they overlap with `mode`.
+    fn blend<I: Image>(self, other: I, mode: BlendMode) -> Composite<Self, I>
+    where
+        Self: Sized,
+    {
+        Composite::new(self, other, Operator::SrcOver, mode)
+    }
+
+    /// Fades the image, scaling its alpha by `opacity` in `[0, 1]`.
+    fn opacity(self, opacity: f32) -> Opacity<Self>
+    where
+        Self: Sized,
+    {
+        Opacity::new(self, opacity)
+    }
+
+    /// Keeps only as much of the image as `mask` covers at each point.
+    fn mask<M: Image>(self, mask: M, mode: MaskMode) -> Mask<Self, M>
+    where
+        Self: Sized,
+    {
+        Mask::new(self, mask, mode)
+    }
+
+    /// Keeps the `rect` window of the image. Outside it the image is
+    /// transparent, or filled as set by [`Crop::with_edge`].
+    fn crop(self, rect: Rect) -> Crop<Self>
+    where
+        Self: Sized,
+    {
+        Crop::new(self, rect)
+    }
+
+    /// Grows the bounds by a margin on each side, so that [`Image::write_to_auto`]
+    /// and [`Image::canvas`] include it. The margin is transparent unless
+    /// [`Pad::with_edge`] fills it from the content. Unbounded content has no
+    /// edges to pad out from and passes through unchanged, and empty content
+    /// stays empty.
+    fn pad(self, left: f32, top: f32, right: f32, bottom: f32) -> Pad<Self>
+    where
+        Self: Sized,
+    {
+        Pad::new(self, left, top, right, bottom)
+    }
+
+    /// Moves the content onto a `width × height` canvas at the origin, its
+    /// [`Image::extent`] placed by `anchor`, and cuts it to fit. The offset is rounded to whole units, halves
+    /// upwards, so pixel-aligned content stays sharp; unbounded content is not
+    /// moved.
+    fn canvas(self, width: usize, height: usize, anchor: Anchor) -> Crop<Transform<Self>>
+    where
+        Self: Sized,
+    {
+        let canvas = Rect::new(0.0, 0.0, width as f32, height as f32);
+        let (dx, dy) = match self.extent() {
+            Some(content) if !content.is_empty() => {
+                let (ax, ay) = anchor.factors();
+                let snap = |v: f32| (v + 0.5).floor();
+                (
+                    snap((canvas.width - content.width) * ax - content.x),
+                    snap((canvas.height - content.height) * ay - content.y),
+                )
+            }
+            _ => (0.0, 0.0),
+        };
+        self.translate(dx, dy).crop(canvas)
+    }
+
+    fn translate(self, x: f32, y: f32) -> Transform<Self>
+    where
+        Self: Sized,
+    {
+        self.transform(Mat3::translate(x, y))
+    }
+
+    /// Rotates by `angle` radians about the origin.
+    fn rotate(self, angle: f32) -> Transform<Self>
+    where
+        Self: Sized,
+    {
+        self.transform(Mat3::rotate(angle))
+    }
+
+    fn scale(self, sx: f32, sy: f32) -> Transform<Self>
     where
         Self: Sized,
     {
-        self.transform([[1.0, 0.0, x], [0.0, 1.0, y], [0.0, 0.0, 1.0]])
+        self.transform(Mat3::scale(sx, sy))
+    }
+
+    fn shear(self, kx: f32, ky: f32) -> Transform<Self>
+    where
+        Self: Sized,
+    {
+        self.transform(Mat3::shear(kx, ky))
+    }
+
+    /// Mirrors the image horizontally within `[0, width]`.
+    fn flip_h(self, width: f32) -> Transform<Self>
+    where
+        Self: Sized,
+    {
+        self.transform(Mat3::translate(width, 0.0) * Mat3::scale(-1.0, 1.0))
+    }
+
+    /// Mirrors the image vertically within `[0, height]`.
+    fn flip_v(self, height: f32) -> Transform<Self>
+    where
+        Self: Sized,
+    {
+        self.transform(Mat3::translate(0.0, height) * Mat3::scale(1.0, -1.0))
+    }
+
+    /// Warps the image so the `src` quadrilateral lands on `dst`. Degenerate
+    /// quadrilaterals leave nothing visible.
+    fn map_quad(self, src: [(f32, f32); 4], dst: [(f32, f32); 4]) -> Transform<Self>
+    where
+        Self: Sized,
+    {
+        self.transform(homography(src, dst).unwrap_or(Mat3::ZERO))
+    }
+
+    fn resample(self, filter: Filter) -> Resample<Self>
+    where
+        Self: Sized,
+    {
+        Resample::new(self, filter)
     }
 
     fn render(&self, width: usize, height: usize) -> Vec<u8> {
+        self.render_with(width, height, &RenderOptions::default())
+    }
+
+    /// Renders the `rect` window of the plane, scaled to `width × height` pixels.
+    fn render_region(&self, rect: Rect, width: usize, height: usize) -> Vec<u8> {
+        let options = RenderOptions {
+            region: Some(rect),
+            ..RenderOptions::default()
+        };
+        self.render_with(width, height, &options)
+    }
+
+    fn render_with(&self, width: usize, height: usize, options: &RenderOptions) -> Vec<u8> {
         let mut buf = vec![0; width * height * 4];
-        for y in 0..height {
-            for x in 0..width {
-                let pixel = self.get(x as f32, y as f32);
-                let idx = (y * width + x) * 4;
-                buf[idx + 0] = (pixel.r * 255.0) as u8;
-                buf[idx + 1] = (pixel.g * 255.0) as u8;
-                buf[idx + 2] = (pixel.b * 255.0) as u8;
-                buf[idx + 3] = (pixel.a * 255.0) as u8;
-            }
-        }
+        render::render_rows::<_, Rgba<u8>>(self, 0..height, width, height, options, &mut buf);
+        buf
+    }
+
+    /// Renders into `buf`, filling all of it, in any supported [`PixelFormat`].
+    fn render_into<P, C>(&self, buf: &mut ImageBuffer<P, C>, options: &RenderOptions)
+    where
+        Self: Sized,
+        P: PixelFormat,
+        C: DerefMut<Target = [P::Subpixel]>,
+    {
+        let (width, height) = (buf.width() as usize, buf.height() as usize);
+        render::render_rows::<_, P>(self, 0..height, width, height, options, buf);
+    }
+
+    /// Renders rows in parallel on the rayon thread pool. The output is
+    /// identical to [`Image::render`].
+    #[cfg(feature = "rayon")]
+    fn par_render(&self, width: usize, height: usize) -> Vec<u8>
+    where
+        Self: Sync,
+    {
+        self.par_render_with(width, height, &RenderOptions::default())
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_render_with(&self, width: usize, height: usize, options: &RenderOptions) -> Vec<u8>
+    where
+        Self: Sync,
+    {
+        let mut buf = vec![0; width * height * 4];
+        render::par_render_rows::<_, Rgba<u8>>(self, width, height, options, &mut buf);
         buf
     }
 
-    fn write_to(&self, path: impl AsRef<Path>, width: usize, height: usize) {
+    fn write_to(&self, path: impl AsRef<Path>, width: usize, height: usize) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let (w, h) = Error::check_dimensions(width, height)?;
         let buf = self.render(width, height);
-        image::save_buffer(
-            path,
-            &buf,
-            width as u32,
-            height as u32,
-            image::ColorType::Rgba8,
-        )
-        .unwrap();
+        image::save_buffer(path, &buf, w, h, image::ColorType::Rgba8).map_err(Error::encode)
+    }
+
+    /// Renders exactly the content extent, [`Image::bounds`] rounded out to
+    /// whole pixels, at one pixel per unit. Returns the region written.
+    fn write_to_auto(&self, path: impl AsRef<Path>) -> Result<Rect, Error>
+    where
+        Self: Sized,
+    {
+        let rect = self.bounds().ok_or(Error::Unbounded)?.round_out();
+        let (width, height) = (rect.width as usize, rect.height as usize);
+        let (w, h) = Error::check_dimensions(width, height)?;
+        let options = RenderOptions {
+            region: Some(rect),
+            ..RenderOptions::default()
+        };
+        let buf = self.render_with(width, height, &options);
+        image::save_buffer(path, &buf, w, h, image::ColorType::Rgba8).map_err(Error::encode)?;
+        Ok(rect)
+    }
+
+    /// Renders `rows` of a `width × height` raster into `buf`, which must hold
+    /// exactly those rows as RGBA8. Lets callers feed their own streaming encoders.
+    /// Error diffusion starts afresh at `rows.start`.
+    fn render_rows(
+        &self,
+        rows: Range<usize>,
+        width: usize,
+        height: usize,
+        options: &RenderOptions,
+        buf: &mut [u8],
+    ) {
+        assert_eq!(buf.len(), rows.len() * width * 4, "buffer size mismatch");
+        render::render_rows::<_, Rgba<u8>>(self, rows, width, height, options, buf);
+    }
+
+    /// Renders indices into `palette`, one byte per pixel, dithered as
+    /// `options.dither` asks.
+    fn render_indexed(
+        &self,
+        width: usize,
+        height: usize,
+        palette: &Palette,
+        options: &RenderOptions,
+    ) -> Vec<u8> {
+        render::render_indexed(self, width, height, palette, options)
+    }
+
+    /// Writes an indexed-colour PNG or GIF using `palette`.
+    fn write_indexed(
+        &self,
+        path: impl AsRef<Path>,
+        width: usize,
+        height: usize,
+        palette: &Palette,
+        options: &RenderOptions,
+    ) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        Error::check_dimensions(width, height)?;
+        let indices = self.render_indexed(width, height, palette, options);
+        indexed::write_indexed(path.as_ref(), &indices, width, height, palette)
+    }
+
+    /// Like [`Image::write_to`], but renders and encodes a band of rows at a
+    /// time so memory stays bounded for huge outputs. Supports PNG and TIFF.
+    fn write_streamed(
+        &self,
+        path: impl AsRef<Path>,
+        width: usize,
+        height: usize,
+        options: &RenderOptions,
+    ) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        stream::write_streamed(self, path.as_ref(), width, height, options)
     }
 }
 
-impl<I: Image> Image for &I {
+impl<I: Image + ?Sized> Image for &I {
     fn get(&self, x: f32, y: f32) -> Pixel {
         I::get(*self, x, y)
     }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        I::sample(*self, x, y, footprint)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        I::bounds(*self)
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        I::extent(*self)
+    }
 }
 
-// impl Image for Box<dyn Image> {
-//     fn get(&self, x: f32, y: f32) -> Pixel {
-//         let img: &dyn Image = &*self;
-//         img.get(x, y)
-//     }
-// }
+impl<I: Image + ?Sized> Image for Box<I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        I::get(self, x, y)
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        I::sample(self, x, y, footprint)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        I::bounds(self)
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        I::extent(self)
+    }
+}
+
+impl<I: Image + ?Sized> Image for Rc<I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        I::get(self, x, y)
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        I::sample(self, x, y, footprint)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        I::bounds(self)
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        I::extent(self)
+    }
+}
+
+impl<I: Image + ?Sized> Image for Arc<I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        I::get(self, x, y)
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        I::sample(self, x, y, footprint)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        I::bounds(self)
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        I::extent(self)
+    }
+}
 
 pub struct Uniform {
     color: Pixel,
@@ -93,25 +576,90 @@ impl Image for Uniform {
     fn get(&self, _x: f32, _y: f32) -> Pixel {
         self.color
     }
+
+    fn bounds(&self) -> Option<Rect> {
+        (self.color.a == 0.0).then(Rect::default)
+    }
 }
 
-struct Transform<I> {
+#[derive(Clone)]
+pub struct Transform<I> {
     image: I,
-    matrix: [[f32; 3]; 3],
+    matrix: Mat3,
 }
 
 impl<I: Image> Transform<I> {
-    fn transform(&self, x: f32, y: f32) -> (f32, f32) {
-        let x2 = x * self.matrix[0][0] + y * self.matrix[0][1] + self.matrix[0][2];
-        let y2 = x * self.matrix[1][0] + y * self.matrix[1][1] + self.matrix[1][2];
-        (x2, y2)
+    /// Applies `matrix` after this transform, folding both into a single matrix
+    /// instead of nesting another `Transform` around this one.
+    pub fn then(self, matrix: impl Into<Mat3>) -> Transform<I> {
+        let inverse = matrix
+            .into()
+            .normalize_sign()
+            .invert()
+            .unwrap_or(Mat3::ZERO);
+        Transform {
+            image: self.image,
+            matrix: self.matrix * inverse,
+        }
+    }
+
+    pub fn try_then(self, matrix: impl Into<Mat3>) -> Result<Transform<I>, SingularMatrix> {
+        let inverse = matrix
+            .into()
+            .normalize_sign()
+            .invert()
+            .ok_or(SingularMatrix)?;
+        Ok(Transform {
+            image: self.image,
+            matrix: self.matrix * inverse,
+        })
+    }
+
+    pub fn by_ref(&self) -> Transform<&I> {
+        Transform {
+            image: &self.image,
+            matrix: self.matrix,
+        }
+    }
+
+    /// How far the source moves per unit step at `(x, y)`, taken from the
+    /// Jacobian of the (possibly projective) map.
+    fn scale(&self, x: f32, y: f32, (x2, y2): (f32, f32)) -> f32 {
+        let m = &self.matrix.0;
+        let w = x * m[2][0] + y * m[2][1] + m[2][2];
+        let dx = ((m[0][0] - x2 * m[2][0]) / w).hypot((m[1][0] - y2 * m[2][0]) / w);
+        let dy = ((m[0][1] - x2 * m[2][1]) / w).hypot((m[1][1] - y2 * m[2][1]) / w);
+        dx.max(dy)
     }
 }
 
 impl<I: Image> Image for Transform<I> {
     fn get(&self, x: f32, y: f32) -> Pixel {
-        let (x2, y2) = self.transform(x, y);
-        self.image.get(x2, y2)
+        match self.matrix.apply(x, y) {
+            Some((x2, y2)) => self.image.get(x2, y2),
+            None => Pixel::TRANSPARENT,
+        }
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        match self.matrix.apply(x, y) {
+            Some(p) => self.image.sample(p.0, p.1, footprint * self.scale(x, y, p)),
+            None => PremulPixel::TRANSPARENT,
+        }
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        let Some(forward) = self.matrix.invert() else {
+            return Some(Rect::default());
+        };
+        self.image.bounds()?.transform(&forward)
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        let Some(forward) = self.matrix.invert() else {
+            return Some(Rect::default());
+        };
+        self.image.extent()?.transform(&forward)
     }
 }
 
@@ -122,25 +670,21 @@ pub struct Join<I1, I2> {
 
 impl<I1: Image, I2: Image> Image for Join<I1, I2> {
     fn get(&self, x: f32, y: f32) -> Pixel {
-        let px1 = self.image1.get(x, y);
-        let px2 = self.image2.get(x, y);
-        let a = px2.a + px1.a * (1.0 - px2.a);
-        if a == 0.0 {
-            return Pixel {
-                r: 0.0,
-                g: 0.0,
-                b: 0.0,
-                a: 0.0,
-            };
-        }
+        self.sample(x, y, 1.0).unpremultiply()
+    }
 
-        let blend = |v1, v2| (v2 * px2.a + v1 * px1.a * (1.0 - px2.a)) / a;
-        Pixel {
-            r: blend(px1.r, px2.r),
-            g: blend(px1.g, px2.g),
-            b: blend(px1.b, px2.b),
-            a,
-        }
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        let px1 = self.image1.sample(x, y, footprint);
+        let px2 = self.image2.sample(x, y, footprint);
+        px2 + px1 * (1.0 - px2.a)
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        rect::union(self.image1.bounds(), self.image2.bounds())
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        rect::union(self.image1.extent(), self.image2.extent())
     }
 }
 
@@ -148,82 +692,284 @@ pub struct BufImage {
     data: Vec<u8>,
     width: usize,
     height: usize,
+    filter: Filter,
+    edge: EdgeMode,
+    color_space: ColorSpace,
+    mipmap: Mipmap,
+    levels: OnceLock<Vec<sampling::Level>>,
 }
 
 impl BufImage {
-    pub fn open(path: impl AsRef<Path>) -> Self {
-        let data = image::ImageReader::open(path)
-            .unwrap()
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let data = image::ImageReader::open(path)?
             .decode()
-            .unwrap()
+            .map_err(Error::decode)?
             .into_rgba8();
+        Ok(BufImage::from_rgba8(
+            data.width() as usize,
+            data.height() as usize,
+            data.into_raw(),
+        ))
+    }
+
+    /// Wraps sRGB-encoded, straight-alpha RGBA bytes in row-major order.
+    pub(crate) fn from_rgba8(width: usize, height: usize, data: Vec<u8>) -> Self {
+        debug_assert_eq!(data.len(), width * height * 4);
         BufImage {
-            width: data.width() as usize,
-            height: data.height() as usize,
-            data: data.into_raw(),
+            width,
+            height,
+            data,
+            filter: Filter::Nearest,
+            edge: EdgeMode::Transparent,
+            color_space: ColorSpace::Srgb,
+            mipmap: Mipmap::None,
+            levels: OnceLock::new(),
         }
     }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_edge(mut self, edge: EdgeMode) -> Self {
+        self.edge = edge;
+        self.levels = OnceLock::new();
+        self
+    }
+
+    /// Sets how the stored bytes are encoded; they are decoded to linear light
+    /// as they are read.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self.levels = OnceLock::new();
+        self
+    }
+
+    /// Enables minification from a lazily built mip pyramid when the image is
+    /// sampled with a footprint larger than one texel.
+    pub fn with_mipmaps(mut self, mipmap: Mipmap) -> Self {
+        self.mipmap = mipmap;
+        self
+    }
+
+    fn levels(&self) -> &[sampling::Level] {
+        self.levels.get_or_init(|| {
+            sampling::pyramid(self.width, self.height, self.edge, |x, y| self.texel(x, y))
+        })
+    }
+
+    fn texel(&self, x: i64, y: i64) -> PremulPixel {
+        self.edge.fetch(x, y, self.width, self.height, |x, y| {
+            let idx = (y * self.width + x) * 4;
+            let r = self.color_space.decode_u8(self.data[idx]);
+            let g = self.color_space.decode_u8(self.data[idx + 1]);
+            let b = self.color_space.decode_u8(self.data[idx + 2]);
+            let a = self.data[idx + 3] as f32 / 255.0;
+
+            Pixel { r, g, b, a }.premultiply()
+        })
+    }
 }
 
 impl Image for BufImage {
     fn get(&self, x: f32, y: f32) -> Pixel {
-        if x < 0.0 || y < 0.0 {
-            return Pixel {
-                r: 0.0,
-                g: 0.0,
-                b: 0.0,
-                a: 0.0,
-            };
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        let base = |x, y| sampling::sample(self.filter, x, y, |x, y| self.texel(x, y));
+        if self.mipmap == Mipmap::None || footprint <= 1.0 {
+            return base(x, y);
         }
 
-        let x = x.round() as usize;
-        let y = y.round() as usize;
-        if x >= self.width || y >= self.height {
-            return Pixel {
-                r: 0.0,
-                g: 0.0,
-                b: 0.0,
-                a: 0.0,
-            };
+        let levels = self.levels();
+        sampling::sample_mipmapped(self.mipmap, levels.len(), x, y, footprint, |n, x, y| {
+            if n == 0 {
+                return base(x, y);
+            }
+            let level = &levels[n - 1];
+            sampling::sample(self.filter, x, y, |x, y| level.texel(x, y, self.edge))
+        })
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        Some(self.extent()?.outset(self.filter.reach()))
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        let rect = Rect::new(0.0, 0.0, self.width as f32, self.height as f32);
+        self.edge.is_transparent().then_some(rect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opaque white left of `x = 1`, transparent to the right.
+    struct HalfPlane;
+
+    impl Image for HalfPlane {
+        fn get(&self, x: f32, _y: f32) -> Pixel {
+            if x < 1.0 {
+                Pixel::from_srgb(1.0, 1.0, 1.0, 1.0)
+            } else {
+                Pixel::TRANSPARENT
+            }
         }
+    }
+
+    #[test]
+    fn negated_matrix_is_the_same_map() {
+        let negated = Mat3([[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]]);
+        assert_eq!(
+            HalfPlane.transform(negated).render(2, 2),
+            HalfPlane.render(2, 2)
+        );
+
+        let perspective = Mat3([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.01, 0.0, 1.0]]);
+        let flipped = Mat3(perspective.0.map(|row| row.map(|v| -v)));
+        assert_eq!(
+            HalfPlane.transform(flipped).render(4, 4),
+            HalfPlane.transform(perspective).render(4, 4)
+        );
+        assert_eq!(
+            HalfPlane.translate(1.0, 0.0).then(negated).render(4, 4),
+            HalfPlane.translate(1.0, 0.0).render(4, 4)
+        );
+    }
+
+    #[test]
+    fn perspective_warp_keeps_content_across_the_horizon() {
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let quad = [
+            (300.0, 300.0),
+            (400.0, 310.0),
+            (390.0, 380.0),
+            (305.0, 330.0),
+        ];
+        let image = BufImage::from_rgba8(10, 10, vec![255; 10 * 10 * 4]);
+        let warped = (&image).map_quad(square, quad);
+        let bounds = warped.bounds().unwrap();
+        assert!((bounds.x - 300.0).abs() < 0.1 && (bounds.y - 300.0).abs() < 0.1);
+        assert!((bounds.width - 100.0).abs() < 0.1 && (bounds.height - 80.0).abs() < 0.1);
+
+        // The quad covers 4700 square units.
+        let rendered = warped.render(512, 512);
+        let opaque = rendered.chunks(4).filter(|p| p[3] == 255).count();
+        assert!((4600..4800).contains(&opaque), "{opaque} opaque pixels");
+
+        let chained = (&image)
+            .translate(0.0, 0.0)
+            .then(homography(square, quad).unwrap());
+        assert_eq!(chained.render(512, 512), rendered);
+    }
 
-        let idx = (y * self.width + x) * 4;
-        let r = self.data[idx] as f32 / 255.0;
-        let g = self.data[idx + 1] as f32 / 255.0;
-        let b = self.data[idx + 2] as f32 / 255.0;
-        let a = self.data[idx + 3] as f32 / 255.0;
-
-        Pixel { r, g, b, a }
-    }
-}
-
-fn invert(matrix: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
-    let mut adjoint = [
-        [
-            matrix[1][1] * matrix[2][2] - matrix[2][1] * matrix[1][2],
-            matrix[0][2] * matrix[2][1] - matrix[0][1] * matrix[2][2],
-            matrix[0][1] * matrix[1][2] - matrix[1][1] * matrix[0][2],
-        ],
-        [
-            matrix[1][2] * matrix[2][0] - matrix[2][2] * matrix[1][0],
-            matrix[0][0] * matrix[2][2] - matrix[0][2] * matrix[2][0],
-            matrix[0][2] * matrix[1][0] - matrix[1][2] * matrix[0][0],
-        ],
-        [
-            matrix[1][0] * matrix[2][1] - matrix[2][0] * matrix[1][1],
-            matrix[0][1] * matrix[2][0] - matrix[0][0] * matrix[2][1],
-            matrix[0][0] * matrix[1][1] - matrix[1][0] * matrix[0][1],
-        ],
-    ];
-    let determinant =
-        matrix[0][0] * adjoint[0][0] + matrix[0][1] * adjoint[1][0] + matrix[0][2] * adjoint[2][0];
-    if determinant == 0.0 {
-        return [[0.0; 3]; 3];
-    }
-    for i in 0..3 {
-        for j in 0..3 {
-            adjoint[i][j] /= determinant;
+    #[test]
+    fn bounds_cover_the_filter_fringe() {
+        for filter in [Filter::Bilinear, Filter::Bicubic, Filter::Lanczos3] {
+            let image = BufImage::from_rgba8(4, 4, vec![255; 4 * 4 * 4]).with_filter(filter);
+            let scaled = (&image).scale(4.0, 4.0).then(Mat3::translate(12.0, 12.0));
+            let bounds = scaled.bounds().unwrap();
+            assert_eq!(scaled.extent(), Some(Rect::new(12.0, 12.0, 16.0, 16.0)));
+
+            let rendered = scaled.render(40, 40);
+            let visible: Vec<_> = (0..40 * 40).filter(|i| rendered[i * 4 + 3] > 0).collect();
+            assert!(
+                visible.iter().any(|i| i % 40 >= 28),
+                "{filter:?} has no fringe"
+            );
+            for i in visible {
+                let (x, y) = ((i % 40) as f32 + 0.5, (i / 40) as f32 + 0.5);
+                assert!(bounds.contains(x, y), "{filter:?} visible at {x}, {y}");
+            }
         }
     }
-    adjoint
+
+    #[test]
+    fn degenerate_quad_leaves_nothing_visible() {
+        let square = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)];
+        let line = [(0.0, 0.0), (2.0, 0.0), (4.0, 0.0), (0.0, 4.0)];
+        let rendered = HalfPlane.map_quad(square, line).render(4, 4);
+        assert!(rendered.chunks(4).all(|p| p[3] == 0));
+    }
+
+    #[test]
+    fn truncated_file_is_a_decode_error() {
+        let mut png = Vec::new();
+        image::RgbaImage::from_pixel(16, 16, image::Rgba([10, 20, 30, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png.truncate(png.len() / 2);
+        let path =
+            std::env::temp_dir().join(format!("imcraft-truncated-{}.png", std::process::id()));
+        std::fs::write(&path, &png).unwrap();
+        let result = BufImage::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            matches!(result, Err(Error::Decode(_))),
+            "{:?}",
+            result.err()
+        );
+
+        let missing = std::env::temp_dir().join("imcraft-does-not-exist.png");
+        assert!(matches!(BufImage::open(missing), Err(Error::Io(_))));
+    }
+
+    fn close(a: Option<Rect>, b: Rect) -> bool {
+        let a = a.unwrap();
+        [a.x - b.x, a.y - b.y, a.width - b.width, a.height - b.height]
+            .iter()
+            .all(|d| d.abs() < 1e-4)
+    }
+
+    #[test]
+    fn bounds_follow_the_image_through_wrappers() {
+        let square = || BufImage::from_rgba8(4, 4, vec![255; 4 * 4 * 4]);
+
+        let turned = square().rotate(std::f32::consts::FRAC_PI_4);
+        let half = 8f32.sqrt();
+        assert!(close(
+            turned.bounds(),
+            Rect::new(-half, 0.0, 2.0 * half, 2.0 * half)
+        ));
+        assert!(close(
+            square().scale(2.0, 0.5).translate(1.0, 3.0).bounds(),
+            Rect::new(1.0, 3.0, 8.0, 2.0)
+        ));
+        assert_eq!(square().scale(0.0, 1.0).bounds(), Some(Rect::default()));
+        assert_eq!(HalfPlane.translate(3.0, 0.0).bounds(), None);
+
+        let apart = square().join(square().translate(10.0, 2.0));
+        assert_eq!(apart.bounds(), Some(Rect::new(0.0, 0.0, 14.0, 6.0)));
+        assert_eq!(square().join(HalfPlane).bounds(), None);
+
+        let overlap = square().composite(square().translate(2.0, 1.0), Operator::SrcIn);
+        assert_eq!(overlap.bounds(), Some(Rect::new(2.0, 1.0, 2.0, 3.0)));
+        let masked = HalfPlane.mask(square().translate(-1.0, 0.0), MaskMode::Alpha);
+        assert_eq!(masked.bounds(), Some(Rect::new(-1.0, 0.0, 4.0, 4.0)));
+    }
+
+    #[test]
+    fn write_to_auto_renders_the_rounded_out_bounds() {
+        let path = std::env::temp_dir().join(format!("imcraft-auto-{}.png", std::process::id()));
+        let image = BufImage::from_rgba8(4, 3, vec![255; 4 * 3 * 4]).translate(1.5, -0.25);
+        let written = image.write_to_auto(&path);
+        let decoded = image::open(&path).map(|image| image.into_rgba8());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written.unwrap(), Rect::new(1.0, -1.0, 5.0, 4.0));
+        let decoded = decoded.unwrap();
+        assert_eq!(decoded.dimensions(), (5, 4));
+        // The content spans [1.5, 5.5) × [-0.25, 2.75); with the region
+        // starting at (1, -1) its texels land in columns 0..4 and rows 1..4.
+        let alpha = |x, y| decoded.get_pixel(x, y).0[3];
+        assert_eq!([alpha(0, 1), alpha(3, 3)], [255, 255]);
+        assert_eq!([alpha(4, 1), alpha(0, 0)], [0, 0]);
+
+        let unbounded = std::env::temp_dir().join("imcraft-auto-unbounded.png");
+        let result = HalfPlane.write_to_auto(&unbounded);
+        assert!(matches!(result, Err(Error::Unbounded)));
+        assert!(!unbounded.exists());
+    }
 }
@@ -0,0 +1,176 @@
+use crate::rect;
+use crate::{Image, Pixel, PremulPixel, Rect};
+
+/// Which part of a mask image decides how much of the masked image survives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaskMode {
+    #[default]
+    Alpha,
+    /// The mask's luminance, weighted by its alpha, so transparent areas hide.
+    Luminance,
+    InvertedAlpha,
+}
+
+impl MaskMode {
+    /// Where the masked image can be visible, given where the image and the
+    /// mask are.
+    fn bounds(self, image: Option<Rect>, mask: Option<Rect>) -> Option<Rect> {
+        match self {
+            MaskMode::Alpha | MaskMode::Luminance => rect::intersect(image, mask),
+            MaskMode::InvertedAlpha => image,
+        }
+    }
+
+    fn coverage(self, mask: PremulPixel) -> f32 {
+        match self {
+            MaskMode::Alpha => mask.a,
+            // Rec. 709 weights; the channels are linear and already scaled by alpha.
+            MaskMode::Luminance => 0.2126 * mask.r + 0.7152 * mask.g + 0.0722 * mask.b,
+            MaskMode::InvertedAlpha => 1.0 - mask.a,
+        }
+    }
+}
+
+pub struct Opacity<I> {
+    image: I,
+    opacity: f32,
+}
+
+impl<I: Image> Opacity<I> {
+    pub(crate) fn new(image: I, opacity: f32) -> Self {
+        Self {
+            image,
+            opacity: opacity.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl<I: Image> Image for Opacity<I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        if self.opacity == 0.0 {
+            return PremulPixel::TRANSPARENT;
+        }
+        self.image.sample(x, y, footprint) * self.opacity
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        if self.opacity == 0.0 {
+            return Some(Rect::default());
+        }
+        self.image.bounds()
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        if self.opacity == 0.0 {
+            return Some(Rect::default());
+        }
+        self.image.extent()
+    }
+}
+
+pub struct Mask<I, M> {
+    image: I,
+    mask: M,
+    mode: MaskMode,
+}
+
+impl<I: Image, M: Image> Mask<I, M> {
+    pub(crate) fn new(image: I, mask: M, mode: MaskMode) -> Self {
+        Self { image, mask, mode }
+    }
+}
+
+impl<I: Image, M: Image> Image for Mask<I, M> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, footprint: f32) -> PremulPixel {
+        let coverage = self.mode.coverage(self.mask.sample(x, y, footprint));
+        let coverage = coverage.clamp(0.0, 1.0);
+        if coverage == 0.0 {
+            return PremulPixel::TRANSPARENT;
+        }
+        self.image.sample(x, y, footprint) * coverage
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        self.mode.bounds(self.image.bounds(), self.mask.bounds())
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        self.mode.bounds(self.image.extent(), self.mask.extent())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufImage, Uniform};
+
+    fn pixel(r: f32, g: f32, b: f32, a: f32) -> Pixel {
+        Pixel { r, g, b, a }
+    }
+
+    fn masked(mask: Pixel, mode: MaskMode) -> Pixel {
+        Uniform::new(pixel(1.0, 0.5, 0.25, 1.0))
+            .mask(Uniform::new(mask), mode)
+            .get(0.5, 0.5)
+    }
+
+    fn opaque(width: usize, height: usize) -> BufImage {
+        BufImage::from_rgba8(width, height, vec![255; width * height * 4])
+    }
+
+    #[test]
+    fn opacity_scales_alpha_only() {
+        let faded = Uniform::new(pixel(1.0, 0.5, 0.25, 0.8)).opacity(0.5);
+        assert_eq!(faded.get(0.5, 0.5), pixel(1.0, 0.5, 0.25, 0.4));
+        assert_eq!(faded.bounds(), None);
+
+        let hidden = opaque(4, 4).opacity(0.0);
+        assert_eq!(hidden.get(0.5, 0.5).a, 0.0);
+        assert_eq!(hidden.bounds(), Some(Rect::default()));
+        assert_eq!(opaque(4, 4).opacity(-1.0).bounds(), Some(Rect::default()));
+        assert_eq!(opaque(4, 4).opacity(2.0).get(0.5, 0.5).a, 1.0);
+    }
+
+    #[test]
+    fn mask_modes_pick_their_coverage() {
+        let half = pixel(1.0, 1.0, 1.0, 0.25);
+        assert_eq!(masked(half, MaskMode::Alpha), pixel(1.0, 0.5, 0.25, 0.25));
+        assert_eq!(masked(half, MaskMode::InvertedAlpha).a, 0.75);
+        assert_eq!(masked(Pixel::TRANSPARENT, MaskMode::InvertedAlpha).a, 1.0);
+
+        let luminance = |mask| masked(mask, MaskMode::Luminance).a;
+        assert_eq!(luminance(pixel(1.0, 1.0, 1.0, 1.0)), 1.0);
+        assert_eq!(luminance(pixel(0.0, 0.0, 0.0, 1.0)), 0.0);
+        assert_eq!(luminance(pixel(1.0, 1.0, 1.0, 0.5)), 0.5);
+        assert_eq!(luminance(pixel(0.0, 1.0, 0.0, 1.0)), 0.7152);
+        assert_eq!(luminance(Pixel::TRANSPARENT), 0.0);
+    }
+
+    #[test]
+    fn mask_bounds_follow_the_mode() {
+        let mask = || opaque(2, 2).translate(3.0, 3.0);
+        let overlap = Some(Rect::new(3.0, 3.0, 1.0, 1.0));
+        assert_eq!(opaque(4, 4).mask(mask(), MaskMode::Alpha).bounds(), overlap);
+        assert_eq!(
+            opaque(4, 4).mask(mask(), MaskMode::Luminance).bounds(),
+            overlap
+        );
+        assert_eq!(
+            opaque(4, 4).mask(mask(), MaskMode::InvertedAlpha).bounds(),
+            Some(Rect::new(0.0, 0.0, 4.0, 4.0))
+        );
+        let unbounded = Uniform::new(pixel(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(
+            unbounded.mask(mask(), MaskMode::Alpha).bounds(),
+            Some(Rect::new(3.0, 3.0, 2.0, 2.0))
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::ops::Mul;
+
+/// How small the determinant may get, relative to the scale of the linear part
+/// and the projective row, before a matrix is treated as singular.
+const SINGULAR_TOLERANCE: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SingularMatrix;
+
+impl fmt::Display for SingularMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "matrix is singular and cannot be inverted")
+    }
+}
+
+impl std::error::Error for SingularMatrix {}
+
+/// A 3×3 matrix acting on homogeneous column vectors `(x, y, 1)`, so `a * b`
+/// applies `b` first and `a` second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat3(pub [[f32; 3]; 3]);
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+    pub const ZERO: Mat3 = Mat3([[0.0; 3]; 3]);
+
+    pub fn translate(x: f32, y: f32) -> Mat3 {
+        Mat3([[1.0, 0.0, x], [0.0, 1.0, y], [0.0, 0.0, 1.0]])
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Mat3 {
+        Mat3([[sx, 0.0, 0.0], [0.0, sy, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    /// Rotates by `angle` radians about the origin. With y pointing down this
+    /// turns clockwise on screen.
+    pub fn rotate(angle: f32) -> Mat3 {
+        let (sin, cos) = angle.sin_cos();
+        Mat3([[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    pub fn rotate_about(angle: f32, x: f32, y: f32) -> Mat3 {
+        Mat3::translate(x, y) * Mat3::rotate(angle) * Mat3::translate(-x, -y)
+    }
+
+    pub fn shear(kx: f32, ky: f32) -> Mat3 {
+        Mat3([[1.0, kx, 0.0], [ky, 1.0, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    /// Reflects across the line through the origin at `angle` radians from the x axis.
+    pub fn reflect(angle: f32) -> Mat3 {
+        let (sin, cos) = (2.0 * angle).sin_cos();
+        Mat3([[cos, sin, 0.0], [sin, -cos, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[2][1] * m[1][2])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Whether the matrix collapses the plane onto a line or point, up to a
+    /// tolerance that does not depend on the matrix's overall scale. The
+    /// translation column is left out of the comparison, so moving far away
+    /// never makes a matrix singular.
+    pub fn is_singular(&self) -> bool {
+        let m = &self.0;
+        let linear = m[0][0].hypot(m[0][1]).hypot(m[1][0].hypot(m[1][1]));
+        let projective = m[2][0].hypot(m[2][1]).hypot(m[2][2]);
+        let volume = linear * linear * projective;
+        volume == 0.0 || self.determinant().abs() <= volume * SINGULAR_TOLERANCE
+    }
+
+    pub fn invert(&self) -> Option<Mat3> {
+        if self.is_singular() {
+            return None;
+        }
+
+        let matrix = &self.0;
+        let mut adjoint = [
+            [
+                matrix[1][1] * matrix[2][2] - matrix[2][1] * matrix[1][2],
+                matrix[0][2] * matrix[2][1] - matrix[0][1] * matrix[2][2],
+                matrix[0][1] * matrix[1][2] - matrix[1][1] * matrix[0][2],
+            ],
+            [
+                matrix[1][2] * matrix[2][0] - matrix[2][2] * matrix[1][0],
+                matrix[0][0] * matrix[2][2] - matrix[0][2] * matrix[2][0],
+                matrix[0][2] * matrix[1][0] - matrix[1][2] * matrix[0][0],
+            ],
+            [
+                matrix[1][0] * matrix[2][1] - matrix[2][0] * matrix[1][1],
+                matrix[0][1] * matrix[2][0] - matrix[0][0] * matrix[2][1],
+                matrix[0][0] * matrix[1][1] - matrix[1][0] * matrix[0][1],
+            ],
+        ];
+        let determinant = matrix[0][0] * adjoint[0][0]
+            + matrix[0][1] * adjoint[1][0]
+            + matrix[0][2] * adjoint[2][0];
+        for row in &mut adjoint {
+            for value in row {
+                *value /= determinant;
+            }
+        }
+        Some(Mat3(adjoint))
+    }
+
+    /// The same projective map scaled so that `m[2][2]` is not negative, which
+    /// puts the origin in front of the horizon as [`Mat3::apply`] sees it.
+    /// Applied to a forward matrix before inverting, the inverse then keeps
+    /// exactly the points the forward map sends in front of its horizon.
+    pub(crate) fn normalize_sign(self) -> Mat3 {
+        if self.0[2][2] < 0.0 {
+            Mat3(self.0.map(|row| row.map(|v| -v)))
+        } else {
+            self
+        }
+    }
+
+    /// Maps a point, returning `None` when it lands on or behind the horizon
+    /// of a perspective matrix.
+    pub fn apply(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let m = &self.0;
+        let w = x * m[2][0] + y * m[2][1] + m[2][2];
+        if w <= 0.0 {
+            return None;
+        }
+        let x2 = (x * m[0][0] + y * m[0][1] + m[0][2]) / w;
+        let y2 = (x * m[1][0] + y * m[1][1] + m[1][2]) / w;
+        Some((x2, y2))
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Mat3 {
+        let mut out = [[0.0; 3]; 3];
+        for (i, row) in out.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Mat3(out)
+    }
+}
+
+impl From<[[f32; 3]; 3]> for Mat3 {
+    fn from(matrix: [[f32; 3]; 3]) -> Self {
+        Mat3(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Mat3, b: Mat3) {
+        for (row, expected) in a.0.iter().zip(b.0) {
+            for (v, e) in row.iter().zip(expected) {
+                assert!((v - e).abs() <= 1e-4 * e.abs().max(1.0), "{a:?} != {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn singularity_ignores_translation_and_overall_scale() {
+        assert!(!Mat3::translate(1e6, 0.0).is_singular());
+        assert!(!Mat3::translate(-3e7, 5e6).is_singular());
+        assert!(!Mat3::scale(1e-4, 1e-4).is_singular());
+        assert!(!Mat3::scale(1e4, 1e4).is_singular());
+        assert!(!(Mat3::translate(1e6, 1e6) * Mat3::rotate(0.3)).is_singular());
+    }
+
+    #[test]
+    fn collapsing_matrices_are_singular() {
+        assert!(Mat3::ZERO.is_singular());
+        assert!(Mat3::scale(1.0, 0.0).is_singular());
+        assert!(Mat3::scale(1.0, 1e-9).is_singular());
+        assert!(Mat3::shear(1.0, 1.0).is_singular());
+        assert!(Mat3([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]]).is_singular());
+    }
+
+    #[test]
+    fn invert_round_trips() {
+        let matrices = [
+            Mat3::IDENTITY,
+            Mat3::translate(1e6, -2e5),
+            Mat3::scale(1e-3, 250.0),
+            Mat3::rotate_about(1.1, 40.0, -7.0),
+            Mat3::shear(0.5, -0.25) * Mat3::reflect(0.7),
+            Mat3([[1.2, 0.1, 30.0], [-0.2, 0.9, 15.0], [0.0004, -0.0002, 1.0]]),
+        ];
+        for m in matrices {
+            let inverse = m.invert().unwrap();
+            assert_close(m * inverse, Mat3::IDENTITY);
+            assert_close(inverse.invert().unwrap(), m);
+        }
+        assert_eq!(Mat3::scale(1.0, 1e-9).invert(), None);
+    }
+}
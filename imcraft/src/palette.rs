@@ -0,0 +1,373 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// How [`Palette::generate`] picks its colours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PaletteMethod {
+    /// Heckbert's median cut: repeatedly halves the most populous, widest
+    /// box of colours at its median.
+    #[default]
+    MedianCut,
+    /// Gervautz and Purgathofer's octree, merging the least used colours
+    /// first. Fast, but may return fewer colours than asked for.
+    Octree,
+    /// Lloyd's k-means, seeded with the median cut palette.
+    KMeans,
+}
+
+/// Between 1 and 256 RGBA8 colours for indexed output, in the encoded colour
+/// space of the rendered raster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<[u8; 4]>,
+    /// Half the mean distance from each colour to its closest neighbour, in
+    /// `[0, 1]` units, which scales ordered dithering so that offsets reach
+    /// about halfway to the next colour.
+    spread: f32,
+}
+
+/// A distinct colour and how many pixels have it.
+#[derive(Clone, Copy)]
+struct Entry {
+    color: [u8; 4],
+    count: u64,
+}
+
+const KMEANS_ITERATIONS: usize = 16;
+
+impl Palette {
+    /// # Panics
+    ///
+    /// If `colors` is empty or holds more than 256 colours.
+    pub fn new(colors: Vec<[u8; 4]>) -> Self {
+        assert!(
+            (1..=256).contains(&colors.len()),
+            "a palette holds 1 to 256 colours"
+        );
+        let spread = colors
+            .iter()
+            .map(|a| {
+                colors
+                    .iter()
+                    .filter(|b| *b != a)
+                    .map(|b| distance(to_f32(*a), to_f32(*b)).sqrt())
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .filter(|d| d.is_finite())
+            .sum::<f32>()
+            / colors.len() as f32
+            / 510.0;
+        Self { colors, spread }
+    }
+
+    /// Picks at most `size` colours (clamped to `1..=256`) representing an
+    /// RGBA8 raster, such as one from [`Image::render`](crate::Image::render).
+    pub fn generate(rgba: &[u8], size: usize, method: PaletteMethod) -> Self {
+        let size = size.clamp(1, 256);
+        let mut histogram = HashMap::new();
+        for pixel in rgba.chunks_exact(4) {
+            // Every fully transparent pixel looks the same.
+            let color = match pixel {
+                [.., 0] => [0; 4],
+                _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            };
+            *histogram.entry(color).or_insert(0) += 1;
+        }
+        let entries: Vec<Entry> = histogram
+            .into_iter()
+            .map(|(color, count)| Entry { color, count })
+            .collect();
+        if entries.is_empty() {
+            return Palette::new(vec![[0; 4]]);
+        }
+
+        let colors = match method {
+            PaletteMethod::MedianCut => median_cut(&entries, size),
+            PaletteMethod::Octree => octree(&entries, size),
+            PaletteMethod::KMeans => k_means(&entries, median_cut(&entries, size)),
+        };
+        Palette::new(colors.into_iter().map(to_u8).collect())
+    }
+
+    pub fn colors(&self) -> &[[u8; 4]] {
+        &self.colors
+    }
+
+    pub(crate) fn spread(&self) -> f32 {
+        self.spread
+    }
+
+    /// The index of the colour closest to `color`, given in `[0, 255]` units.
+    pub(crate) fn nearest(&self, color: [f32; 4]) -> usize {
+        nearest(self.colors.iter().map(|c| to_f32(*c)), color)
+    }
+}
+
+fn to_f32(color: [u8; 4]) -> [f32; 4] {
+    color.map(|v| v as f32)
+}
+
+fn to_u8(color: [f32; 4]) -> [u8; 4] {
+    color.map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+fn distance(a: [f32; 4], b: [f32; 4]) -> f32 {
+    (0..4).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+fn nearest(colors: impl Iterator<Item = [f32; 4]>, color: [f32; 4]) -> usize {
+    colors
+        .map(|c| distance(c, color))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+fn mean(entries: &[Entry]) -> [f32; 4] {
+    let mut sum = [0.0f64; 4];
+    let mut total = 0.0;
+    for e in entries {
+        for (s, v) in sum.iter_mut().zip(e.color) {
+            *s += v as f64 * e.count as f64;
+        }
+        total += e.count as f64;
+    }
+    sum.map(|v| (v / total) as f32)
+}
+
+fn median_cut(entries: &[Entry], size: usize) -> Vec<[f32; 4]> {
+    // Each box with the channel it spans widest and its priority for splitting.
+    let measure = |b: Vec<Entry>| {
+        let (channel, range) = (0..4)
+            .map(|c| {
+                let values = b.iter().map(|e| e.color[c]);
+                (c, values.clone().max().unwrap() - values.min().unwrap())
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap();
+        let count: u64 = b.iter().map(|e| e.count).sum();
+        (b, channel, range as u64 * count)
+    };
+    let mut boxes = vec![measure(entries.to_vec())];
+    while boxes.len() < size {
+        let Some(i) = (0..boxes.len())
+            .filter(|&i| boxes[i].0.len() > 1)
+            .max_by_key(|&i| boxes[i].2)
+        else {
+            break;
+        };
+
+        let (mut b, channel, _) = boxes.swap_remove(i);
+        b.sort_unstable_by_key(|e| e.color[channel]);
+        let half = b.iter().map(|e| e.count).sum::<u64>().div_ceil(2);
+        let mut seen = 0;
+        let split = b
+            .iter()
+            .position(|e| {
+                seen += e.count;
+                seen >= half
+            })
+            .map_or(1, |k| k + 1)
+            .clamp(1, b.len() - 1);
+        let rest = b.split_off(split);
+        boxes.push(measure(b));
+        boxes.push(measure(rest));
+    }
+    boxes.iter().map(|(b, ..)| mean(b)).collect()
+}
+
+struct Node {
+    sum: [u64; 4],
+    count: u64,
+    depth: u8,
+    parent: usize,
+    /// Child indices, with 0 (the root) meaning none.
+    children: [usize; 16],
+    leaf: bool,
+}
+
+fn octree(entries: &[Entry], size: usize) -> Vec<[f32; 4]> {
+    let mut nodes = vec![Node {
+        sum: [0; 4],
+        count: 0,
+        depth: 0,
+        parent: 0,
+        children: [0; 16],
+        leaf: false,
+    }];
+    for e in entries {
+        let mut node = 0;
+        for depth in 0..=8u8 {
+            for c in 0..4 {
+                nodes[node].sum[c] += e.color[c] as u64 * e.count;
+            }
+            nodes[node].count += e.count;
+            if depth == 8 {
+                break;
+            }
+            let bit = 7 - depth;
+            let slot = (0..4).fold(0, |s, c| s << 1 | (e.color[c] >> bit & 1) as usize);
+            if nodes[node].children[slot] == 0 {
+                nodes[node].children[slot] = nodes.len();
+                nodes.push(Node {
+                    sum: [0; 4],
+                    count: 0,
+                    depth: depth + 1,
+                    parent: node,
+                    children: [0; 16],
+                    leaf: depth + 1 == 8,
+                });
+            }
+            node = nodes[node].children[slot];
+        }
+    }
+
+    // Fold the deepest, least used nodes into their parents until few enough
+    // leaves remain.
+    let reducible = |nodes: &[Node], n: usize| {
+        !nodes[n].leaf
+            && nodes[n]
+                .children
+                .iter()
+                .all(|&child| child == 0 || nodes[child].leaf)
+    };
+    let mut leaves = entries.len();
+    let mut heap: BinaryHeap<_> = (0..nodes.len())
+        .filter(|&n| reducible(&nodes, n))
+        .map(|n| (nodes[n].depth, Reverse(nodes[n].count), n))
+        .collect();
+    while leaves > size {
+        let Some((.., n)) = heap.pop() else { break };
+        leaves -= nodes[n].children.iter().filter(|&&c| c != 0).count() - 1;
+        nodes[n].leaf = true;
+        nodes[n].children = [0; 16];
+        let parent = nodes[n].parent;
+        if n != 0 && reducible(&nodes, parent) {
+            heap.push((nodes[parent].depth, Reverse(nodes[parent].count), parent));
+        }
+    }
+
+    let mut colors = Vec::new();
+    let mut stack = vec![0];
+    while let Some(n) = stack.pop() {
+        let node = &nodes[n];
+        if node.leaf {
+            colors.push(node.sum.map(|v| v as f32 / node.count as f32));
+        } else {
+            stack.extend(node.children.iter().filter(|&&c| c != 0));
+        }
+    }
+    colors
+}
+
+fn k_means(entries: &[Entry], mut centres: Vec<[f32; 4]>) -> Vec<[f32; 4]> {
+    let mut assignment = vec![usize::MAX; entries.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (e, a) in entries.iter().zip(&mut assignment) {
+            let i = nearest(centres.iter().copied(), to_f32(e.color));
+            changed |= *a != i;
+            *a = i;
+        }
+        if !changed {
+            break;
+        }
+
+        let mut clusters = vec![Vec::new(); centres.len()];
+        for (e, &a) in entries.iter().zip(&assignment) {
+            clusters[a].push(*e);
+        }
+        for (centre, cluster) in centres.iter_mut().zip(&clusters) {
+            if !cluster.is_empty() {
+                *centre = mean(cluster);
+            }
+        }
+    }
+    centres
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHODS: [PaletteMethod; 3] = [
+        PaletteMethod::MedianCut,
+        PaletteMethod::Octree,
+        PaletteMethod::KMeans,
+    ];
+
+    /// A 64 × 64 raster sweeping red and green, with blue varying per block.
+    fn gradient() -> Vec<u8> {
+        (0..64 * 64)
+            .flat_map(|i| {
+                let (x, y) = (i % 64, i / 64);
+                [x as u8 * 4, y as u8 * 4, ((x / 8 + y / 8) * 16) as u8, 255]
+            })
+            .collect()
+    }
+
+    fn worst_error(rgba: &[u8], palette: &Palette) -> f32 {
+        rgba.chunks_exact(4)
+            .map(|p| {
+                let color = to_f32([p[0], p[1], p[2], p[3]]);
+                let i = palette.nearest(color);
+                distance(to_f32(palette.colors()[i]), color).sqrt()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn palettes_stay_within_the_requested_size() {
+        let rgba = gradient();
+        for method in METHODS {
+            for size in [0, 1, 2, 16, 255, 256, 1000] {
+                let len = Palette::generate(&rgba, size, method).colors().len();
+                assert!(
+                    (1..=size.clamp(1, 256)).contains(&len),
+                    "{method:?} gave {len} colours for {size}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn palettes_cover_the_input() {
+        let rgba = gradient();
+        for method in METHODS {
+            let coarse = worst_error(&rgba, &Palette::generate(&rgba, 8, method));
+            let fine = worst_error(&rgba, &Palette::generate(&rgba, 128, method));
+            assert!(fine < coarse, "{method:?}: {fine} vs {coarse}");
+            assert!(fine < 64.0, "{method:?} leaves a colour {fine} away");
+        }
+    }
+
+    #[test]
+    fn few_colours_are_kept_exactly() {
+        let colors = [
+            [255, 0, 0, 255],
+            [0, 128, 255, 255],
+            [10, 20, 30, 128],
+            [0, 0, 0, 0],
+        ];
+        let rgba: Vec<u8> = (0..100)
+            .flat_map(|i| colors[i % 7 % 4])
+            // Fully transparent pixels collapse to one colour whatever their RGB.
+            .chain([9, 9, 9, 0])
+            .collect();
+        for method in METHODS {
+            let palette = Palette::generate(&rgba, 4, method);
+            let mut got = palette.colors().to_vec();
+            let mut want = colors.to_vec();
+            got.sort_unstable();
+            want.sort_unstable();
+            assert_eq!(got, want, "{method:?}");
+        }
+    }
+
+    #[test]
+    fn empty_input_gives_one_transparent_colour() {
+        for method in METHODS {
+            assert_eq!(Palette::generate(&[], 16, method).colors(), [[0; 4]]);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::Mat3;
+
+/// An axis-aligned rectangle in the image plane.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    /// Whether `(x, y)` lies inside, counting the left and top edges only.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.width > 0.0 && self.height > 0.0)
+    }
+
+    /// The smallest rectangle covering both. Empty rectangles add nothing.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(
+            x,
+            y,
+            self.right().max(other.right()) - x,
+            self.bottom().max(other.bottom()) - y,
+        )
+    }
+
+    /// The overlap of both, or an empty rectangle if there is none.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let rect = Rect::new(
+            x,
+            y,
+            self.right().min(other.right()) - x,
+            self.bottom().min(other.bottom()) - y,
+        );
+        if rect.is_empty() {
+            Rect::default()
+        } else {
+            rect
+        }
+    }
+
+    /// Moves every edge outwards by `amount`.
+    pub fn outset(&self, amount: f32) -> Rect {
+        if self.is_empty() {
+            return *self;
+        }
+        Rect::new(
+            self.x - amount,
+            self.y - amount,
+            self.width + 2.0 * amount,
+            self.height + 2.0 * amount,
+        )
+    }
+
+    /// The smallest rectangle with integer edges covering this one.
+    pub fn round_out(&self) -> Rect {
+        if self.is_empty() {
+            return Rect::default();
+        }
+        let x = self.x.floor();
+        let y = self.y.floor();
+        Rect::new(x, y, self.right().ceil() - x, self.bottom().ceil() - y)
+    }
+
+    /// The bounding box of the rectangle mapped through `matrix`, or `None` if
+    /// part of it lands behind the horizon of a projective map.
+    pub fn transform(&self, matrix: &Mat3) -> Option<Rect> {
+        if self.is_empty() {
+            return Some(Rect::default());
+        }
+        let corners = [
+            (self.x, self.y),
+            (self.right(), self.y),
+            (self.x, self.bottom()),
+            (self.right(), self.bottom()),
+        ];
+        let mut points = corners.into_iter().map(|(x, y)| matrix.apply(x, y));
+        let first = points.next()??;
+        let (mut min, mut max) = (first, first);
+        for p in points {
+            let (x, y) = p?;
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        Some(Rect::new(min.0, min.1, max.0 - min.0, max.1 - min.1))
+    }
+}
+
+/// Unions bounds where `None` stands for the whole plane.
+pub(crate) fn union(a: Option<Rect>, b: Option<Rect>) -> Option<Rect> {
+    Some(a?.union(&b?))
+}
+
+/// Intersects bounds where `None` stands for the whole plane.
+pub(crate) fn intersect(a: Option<Rect>, b: Option<Rect>) -> Option<Rect> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersect(&b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
@@ -0,0 +1,336 @@
+use std::ops::Range;
+
+use image::Rgba;
+
+use crate::dither::Ditherer;
+use crate::{ColorSpace, Dither, Image, Mat3, Palette, PixelFormat, PremulPixel, Rect, Transform};
+
+/// How many samples are taken per output pixel, per axis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Samples {
+    #[default]
+    Single,
+    Grid(u32),
+    /// One sample at a random position inside each cell of an `n × n` grid.
+    Jittered(u32),
+}
+
+impl Samples {
+    fn per_axis(self) -> u32 {
+        match self {
+            Samples::Single => 1,
+            Samples::Grid(n) | Samples::Jittered(n) => n.max(1),
+        }
+    }
+}
+
+/// The filter used to combine samples into an output pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Reconstruction {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+}
+
+impl Reconstruction {
+    fn radius(self) -> f32 {
+        match self {
+            Reconstruction::Box => 0.5,
+            Reconstruction::Tent => 1.0,
+            Reconstruction::Gaussian => 1.5,
+        }
+    }
+
+    fn weight(self, d: f32) -> f32 {
+        match self {
+            Reconstruction::Box => 1.0,
+            Reconstruction::Tent => 1.0 - d.abs(),
+            Reconstruction::Gaussian => (-2.0 * d * d).exp(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderOptions {
+    pub samples: Samples,
+    pub filter: Reconstruction,
+    pub color_space: ColorSpace,
+    /// The window of the image plane stretched over the output. `None` renders
+    /// the plane from the origin at one unit per pixel.
+    pub region: Option<Rect>,
+    /// Applied when quantizing to integer formats.
+    pub dither: Dither,
+}
+
+pub(crate) fn render_pixel<I: Image + ?Sized>(
+    image: &I,
+    x: usize,
+    y: usize,
+    options: &RenderOptions,
+) -> PremulPixel {
+    let cx = x as f32 + 0.5;
+    let cy = y as f32 + 0.5;
+    if options.samples == Samples::Single && options.filter == Reconstruction::Box {
+        return image.sample(cx, cy, 1.0);
+    }
+
+    // Samples live on a global grid of `n` cells per pixel, so neighbouring
+    // pixels with overlapping filters share (and agree on) the same samples.
+    let n = options.samples.per_axis() as i64;
+    let step = 1.0 / n as f32;
+    let radius = options.filter.radius();
+    let reach = (radius * n as f32).ceil() as i64;
+    let (gx, gy) = (x as i64 * n, y as i64 * n);
+
+    let mut acc = PremulPixel::TRANSPARENT;
+    let mut total = 0.0;
+    for j in gy - reach..gy + n + reach {
+        for i in gx - reach..gx + n + reach {
+            let (jx, jy) = match options.samples {
+                Samples::Jittered(_) => (hash(i, j, 0), hash(i, j, 1)),
+                _ => (0.5, 0.5),
+            };
+            let sx = (i as f32 + jx) * step;
+            let sy = (j as f32 + jy) * step;
+            let (dx, dy) = (sx - cx, sy - cy);
+            if dx.abs() >= radius || dy.abs() >= radius {
+                continue;
+            }
+
+            let w = options.filter.weight(dx) * options.filter.weight(dy);
+            acc += image.sample(sx, sy, step) * w;
+            total += w;
+        }
+    }
+
+    if total <= 0.0 {
+        return PremulPixel::TRANSPARENT;
+    }
+    let acc = acc * (1.0 / total);
+    PremulPixel {
+        r: acc.r.max(0.0),
+        g: acc.g.max(0.0),
+        b: acc.b.max(0.0),
+        a: acc.a.max(0.0),
+    }
+}
+
+/// Renders `rows` of a `width × height` raster of `P` pixels into `buf`, which
+/// holds exactly those rows.
+pub(crate) fn render_rows<I: Image + ?Sized, P: PixelFormat>(
+    image: &I,
+    rows: Range<usize>,
+    width: usize,
+    height: usize,
+    options: &RenderOptions,
+    buf: &mut [P::Subpixel],
+) {
+    let row_len = width * P::CHANNEL_COUNT as usize;
+    if row_len == 0 {
+        return;
+    }
+    let mut dither = Ditherer::new(options.dither, width);
+    for (y, row) in rows.zip(buf.chunks_exact_mut(row_len)) {
+        render_row::<_, P>(image, y, height, options, &mut dither, row);
+    }
+}
+
+/// Like [`render_rows`] for the whole raster, with rows rendered on the rayon
+/// thread pool. Error diffusion still runs in order, so the output is the same.
+#[cfg(feature = "rayon")]
+pub(crate) fn par_render_rows<I: Image + Sync + ?Sized, P: PixelFormat>(
+    image: &I,
+    width: usize,
+    height: usize,
+    options: &RenderOptions,
+    buf: &mut [P::Subpixel],
+) where
+    P::Subpixel: Send,
+{
+    use rayon::prelude::*;
+
+    let row_len = width * P::CHANNEL_COUNT as usize;
+    if row_len == 0 {
+        return;
+    }
+    if !options.dither.is_diffusion() {
+        buf.par_chunks_exact_mut(row_len)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let mut dither = Ditherer::new(options.dither, width);
+                render_row::<_, P>(image, y, height, options, &mut dither, row);
+            });
+        return;
+    }
+
+    let mut shaded = vec![[0.0; 4]; width * height];
+    shaded
+        .par_chunks_exact_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| shade_row::<_, P>(image, y, height, options, row));
+    let mut dither = Ditherer::new(options.dither, width);
+    let rows = shaded
+        .chunks_exact_mut(width)
+        .zip(buf.chunks_exact_mut(row_len));
+    for (y, (shaded, row)) in rows.enumerate() {
+        quantize_row::<P>(y, shaded, &mut dither, row);
+    }
+}
+
+/// Renders a `width × height` raster of indices into `palette`.
+pub(crate) fn render_indexed<I: Image + ?Sized>(
+    image: &I,
+    width: usize,
+    height: usize,
+    palette: &Palette,
+    options: &RenderOptions,
+) -> Vec<u8> {
+    let mut indices = Vec::with_capacity(width * height);
+    let mut dither = Ditherer::new(options.dither, width);
+    let mut shaded = vec![[0.0; 4]; width];
+    for y in 0..height {
+        shade_row::<_, Rgba<u8>>(image, y, height, options, &mut shaded);
+        dither.dither_row(y, &mut shaded, palette.spread(), |c| {
+            let i = palette.nearest(c.map(|v| v * 255.0));
+            indices.push(i as u8);
+            palette.colors()[i].map(|v| v as f32 / 255.0)
+        });
+    }
+    indices
+}
+
+/// Renders row `y` of a `height`-row raster of `P` pixels into `row`.
+pub(crate) fn render_row<I: Image + ?Sized, P: PixelFormat>(
+    image: &I,
+    y: usize,
+    height: usize,
+    options: &RenderOptions,
+    dither: &mut Ditherer,
+    row: &mut [P::Subpixel],
+) {
+    let mut shaded = vec![[0.0; 4]; row.len() / P::CHANNEL_COUNT as usize];
+    shade_row::<_, P>(image, y, height, options, &mut shaded);
+    quantize_row::<P>(y, &mut shaded, dither, row);
+}
+
+/// Fills `row` with the encoded, unquantized channels of row `y`.
+fn shade_row<I: Image + ?Sized, P: PixelFormat>(
+    image: &I,
+    y: usize,
+    height: usize,
+    options: &RenderOptions,
+    row: &mut [[f32; 4]],
+) {
+    let Some(rect) = options.region else {
+        return shade_pixels::<_, P>(image, y, options, row);
+    };
+    let width = row.len();
+    let view = Transform {
+        image,
+        matrix: Mat3::translate(rect.x, rect.y)
+            * Mat3::scale(rect.width / width as f32, rect.height / height as f32),
+    };
+    shade_pixels::<_, P>(&view, y, options, row);
+}
+
+fn shade_pixels<I: Image + ?Sized, P: PixelFormat>(
+    image: &I,
+    y: usize,
+    options: &RenderOptions,
+    row: &mut [[f32; 4]],
+) {
+    for (x, out) in row.iter_mut().enumerate() {
+        *out = P::encode(render_pixel(image, x, y, options), options.color_space);
+    }
+}
+
+fn quantize_row<P: PixelFormat>(
+    y: usize,
+    shaded: &mut [[f32; 4]],
+    dither: &mut Ditherer,
+    row: &mut [P::Subpixel],
+) {
+    if let Some(max) = P::MAX {
+        dither.dither_row(y, shaded, 1.0 / max, |c| {
+            c.map(|v| (v * max).round().clamp(0.0, max) / max)
+        });
+    }
+    let channels = P::CHANNEL_COUNT as usize;
+    for (c, out) in shaded.iter().zip(row.chunks_exact_mut(channels)) {
+        *P::from_slice_mut(out) = P::from_encoded(*c);
+    }
+}
+
+/// A deterministic value in `[0, 1)` for a grid cell, so jittered renders are reproducible.
+fn hash(i: i64, j: i64, salt: u64) -> f32 {
+    let mut h = (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (j as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ salt.wrapping_mul(0x1656_67b1_9e37_79f9);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufImage, Filter};
+
+    /// A smooth colour ramp, stretched so that most samples fall between texels.
+    fn ramp() -> Transform<BufImage> {
+        let data = (0..16 * 16)
+            .flat_map(|i| {
+                [
+                    (i % 16 * 17) as u8,
+                    (i / 16 * 17) as u8,
+                    128,
+                    255 - i as u8 / 2,
+                ]
+            })
+            .collect();
+        BufImage::from_rgba8(16, 16, data)
+            .with_filter(Filter::Bilinear)
+            .scale(1.7, 1.3)
+    }
+
+    #[test]
+    fn ordered_dithering_renders_the_same_in_bands() {
+        let image = ramp();
+        for dither in [Dither::None, Dither::Bayer, Dither::BlueNoise] {
+            let options = RenderOptions {
+                dither,
+                ..RenderOptions::default()
+            };
+            let mut banded = vec![0; 27 * 21 * 4];
+            for (band, rows) in banded.chunks_mut(27 * 4 * 8).zip([0..8, 8..16, 16..21]) {
+                image.render_rows(rows, 27, 21, &options, band);
+            }
+            assert_eq!(banded, image.render_with(27, 21, &options), "{dither:?}");
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_render_matches_serial_render() {
+        let image = ramp();
+        for dither in [Dither::None, Dither::Bayer, Dither::FloydSteinberg] {
+            for samples in [Samples::Grid(3), Samples::Jittered(2)] {
+                let options = RenderOptions {
+                    samples,
+                    filter: Reconstruction::Tent,
+                    dither,
+                    ..RenderOptions::default()
+                };
+                assert_eq!(
+                    image.par_render_with(27, 21, &options),
+                    image.render_with(27, 21, &options),
+                    "{dither:?} with {samples:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use crate::{Image, Pixel, PremulPixel, Rect};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    #[default]
+    Nearest,
+    Bilinear,
+    Bicubic,
+    Lanczos3,
+}
+
+impl Filter {
+    fn radius(self) -> i64 {
+        match self {
+            Filter::Nearest => 0,
+            Filter::Bilinear => 1,
+            Filter::Bicubic => 2,
+            Filter::Lanczos3 => 3,
+        }
+    }
+
+    /// How far past the outermost texel edges the filter spreads content.
+    pub(crate) fn reach(self) -> f32 {
+        (self.radius() as f32 - 0.5).max(0.0)
+    }
+
+    fn weight(self, t: f32) -> f32 {
+        let t = t.abs();
+        match self {
+            Filter::Nearest => 1.0,
+            Filter::Bilinear => (1.0 - t).max(0.0),
+            // Catmull-Rom, i.e. the cubic convolution kernel with a = -0.5.
+            Filter::Bicubic => {
+                if t < 1.0 {
+                    1.5 * t * t * t - 2.5 * t * t + 1.0
+                } else if t < 2.0 {
+                    -0.5 * t * t * t + 2.5 * t * t - 4.0 * t + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos3 => {
+                if t < 3.0 {
+                    sinc(t) * sinc(t / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(t: f32) -> f32 {
+    if t == 0.0 {
+        return 1.0;
+    }
+    let t = t * std::f32::consts::PI;
+    t.sin() / t
+}
+
+/// 2²³, beyond which `f32` cannot tell adjacent texels apart.
+const FAR: f32 = 8_388_608.0;
+
+/// Reconstructs a continuous image from a grid of texels, where texel `(i, j)`
+/// covers the unit square starting at `(i, j)` and is centred at `(i + 0.5, j + 0.5)`.
+pub(crate) fn sample(
+    filter: Filter,
+    x: f32,
+    y: f32,
+    texel: impl Fn(i64, i64) -> PremulPixel,
+) -> PremulPixel {
+    if x.is_nan() || y.is_nan() {
+        return PremulPixel::TRANSPARENT;
+    }
+    let u = x - 0.5;
+    let v = y - 0.5;
+    // Past `FAR` neighbouring texels are no longer apart in `f32`, and near
+    // infinity the tap indices would overflow, so take the nearest texel;
+    // the casts saturate, which lands on the edge handling.
+    if filter == Filter::Nearest || u.abs().max(v.abs()) >= FAR {
+        return texel(x.floor() as i64, y.floor() as i64);
+    }
+
+    let radius = filter.radius();
+    let i0 = u.floor() as i64 - radius + 1;
+    let j0 = v.floor() as i64 - radius + 1;
+    let taps = (2 * radius) as usize;
+
+    let mut wx = [0.0; 6];
+    let mut wy = [0.0; 6];
+    for k in 0..taps {
+        wx[k] = filter.weight(u - (i0 + k as i64) as f32);
+        wy[k] = filter.weight(v - (j0 + k as i64) as f32);
+    }
+    let sum_x: f32 = wx[..taps].iter().sum();
+    let sum_y: f32 = wy[..taps].iter().sum();
+
+    let mut acc = PremulPixel::TRANSPARENT;
+    for (dj, wy) in wy[..taps].iter().enumerate() {
+        for (di, wx) in wx[..taps].iter().enumerate() {
+            let w = wx * wy;
+            if w == 0.0 {
+                continue;
+            }
+            acc += texel(i0 + di as i64, j0 + dj as i64) * w;
+        }
+    }
+
+    // Negative lobes can overshoot, so pull the result back into gamut.
+    let acc = acc * (1.0 / (sum_x * sum_y));
+    let a = acc.a.clamp(0.0, 1.0);
+    PremulPixel {
+        r: acc.r.clamp(0.0, a),
+        g: acc.g.clamp(0.0, a),
+        b: acc.b.clamp(0.0, a),
+        a,
+    }
+}
+
+pub struct Resample<I> {
+    image: I,
+    filter: Filter,
+}
+
+impl<I: Image> Resample<I> {
+    pub(crate) fn new(image: I, filter: Filter) -> Self {
+        Self { image, filter }
+    }
+}
+
+impl<I: Image> Image for Resample<I> {
+    fn get(&self, x: f32, y: f32) -> Pixel {
+        self.sample(x, y, 1.0).unpremultiply()
+    }
+
+    fn sample(&self, x: f32, y: f32, _footprint: f32) -> PremulPixel {
+        sample(self.filter, x, y, |i, j| {
+            self.image.sample(i as f32 + 0.5, j as f32 + 0.5, 1.0)
+        })
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        let bounds = self.image.bounds()?.round_out();
+        Some(bounds.outset(self.filter.reach()))
+    }
+
+    fn extent(&self) -> Option<Rect> {
+        Some(self.image.extent()?.round_out())
+    }
+}
+
+/// What a texel grid returns for coordinates outside its bounds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EdgeMode {
+    #[default]
+    Transparent,
+    Clamp,
+    Repeat,
+    Mirror,
+    Border(Pixel),
+}
+
+impl EdgeMode {
+    /// Whether everything outside the texel grid is transparent.
+    pub(crate) fn is_transparent(self) -> bool {
+        match self {
+            EdgeMode::Transparent => true,
+            EdgeMode::Border(pixel) => pixel.a == 0.0,
+            _ => false,
+        }
+    }
+
+    fn wrap(self, i: i64, len: usize) -> Option<usize> {
+        let len = len as i64;
+        if len == 0 {
+            return None;
+        }
+        let i = match self {
+            EdgeMode::Transparent | EdgeMode::Border(_) => {
+                if i < 0 || i >= len {
+                    return None;
+                }
+                i
+            }
+            EdgeMode::Clamp => i.clamp(0, len - 1),
+            EdgeMode::Repeat => i.rem_euclid(len),
+            EdgeMode::Mirror => {
+                let i = i.rem_euclid(2 * len);
+                if i < len {
+                    i
+                } else {
+                    2 * len - 1 - i
+                }
+            }
+        };
+        Some(i as usize)
+    }
+
+    /// Resolves `(x, y)` on a `width × height` grid and fetches it with `texel`,
+    /// falling back to the border colour.
+    pub(crate) fn fetch(
+        self,
+        x: i64,
+        y: i64,
+        width: usize,
+        height: usize,
+        texel: impl Fn(usize, usize) -> PremulPixel,
+    ) -> PremulPixel {
+        match (self.wrap(x, width), self.wrap(y, height)) {
+            (Some(x), Some(y)) => texel(x, y),
+            _ => match self {
+                EdgeMode::Border(color) => color.premultiply(),
+                _ => PremulPixel::TRANSPARENT,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mipmap {
+    #[default]
+    None,
+    Nearest,
+    Linear,
+}
+
+/// A box-filtered reduction of an image to half its size.
+pub(crate) struct Level {
+    width: usize,
+    height: usize,
+    data: Vec<PremulPixel>,
+}
+
+impl Level {
+    fn downsample(width: usize, height: usize, texel: impl Fn(i64, i64) -> PremulPixel) -> Level {
+        let width = width.div_ceil(2);
+        let height = height.div_ceil(2);
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let mut acc = PremulPixel::TRANSPARENT;
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    acc += texel(2 * x + dx, 2 * y + dy) * 0.25;
+                }
+                data.push(acc);
+            }
+        }
+        Level {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub(crate) fn texel(&self, x: i64, y: i64, edge: EdgeMode) -> PremulPixel {
+        edge.fetch(x, y, self.width, self.height, |x, y| {
+            self.data[y * self.width + x]
+        })
+    }
+}
+
+/// Builds every level below the base image, down to a single texel.
+pub(crate) fn pyramid(
+    width: usize,
+    height: usize,
+    edge: EdgeMode,
+    texel: impl Fn(i64, i64) -> PremulPixel,
+) -> Vec<Level> {
+    let mut levels: Vec<Level> = Vec::new();
+    let (mut w, mut h) = (width, height);
+    while w > 1 || h > 1 {
+        let level = match levels.last() {
+            Some(prev) => Level::downsample(w, h, |x, y| prev.texel(x, y, edge)),
+            None => Level::downsample(w, h, &texel),
+        };
+        (w, h) = (level.width, level.height);
+        levels.push(level);
+    }
+    levels
+}
+
+/// Picks the pyramid level(s) for a footprint given in base texels and blends them.
+/// `level(n, x, y)` samples level `n` at that level's coordinates, level 0 being the base.
+pub(crate) fn sample_mipmapped(
+    mipmap: Mipmap,
+    levels: usize,
+    x: f32,
+    y: f32,
+    footprint: f32,
+    level: impl Fn(usize, f32, f32) -> PremulPixel,
+) -> PremulPixel {
+    if footprint <= 1.0 {
+        return level(0, x, y);
+    }
+
+    let lod = footprint.log2().min(levels as f32);
+    let at = |n: usize| {
+        let scale = (1 << n) as f32;
+        level(n, x / scale, y / scale)
+    };
+    match mipmap {
+        Mipmap::None => level(0, x, y),
+        Mipmap::Nearest => at(lod.round() as usize),
+        Mipmap::Linear => {
+            let lo = lod.floor() as usize;
+            let t = lod - lo as f32;
+            if t == 0.0 {
+                return at(lo);
+            }
+            at(lo) * (1.0 - t) + at(lo + 1) * t
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufImage, ColorSpace, Image, Pixel};
+
+    fn opaque(width: usize, height: usize) -> BufImage {
+        BufImage::from_rgba8(width, height, vec![255; width * height * 4])
+    }
+
+    #[test]
+    fn extreme_coordinates_do_not_overflow() {
+        let far = [f32::INFINITY, f32::NEG_INFINITY, 1e19, -1e19, 9e6, f32::MAX];
+        for filter in [
+            Filter::Nearest,
+            Filter::Bilinear,
+            Filter::Bicubic,
+            Filter::Lanczos3,
+        ] {
+            let image = opaque(4, 4).with_filter(filter);
+            let clamped = opaque(4, 4).with_filter(filter).with_edge(EdgeMode::Clamp);
+            for v in far {
+                assert_eq!(image.get(v, 0.5).a, 0.0, "{filter:?} at {v}");
+                assert_eq!(image.get(0.5, v).a, 0.0, "{filter:?} at {v}");
+                assert_eq!(clamped.get(v, v).a, 1.0, "{filter:?} at {v}");
+            }
+            assert_eq!(clamped.get(f32::NAN, 0.5).a, 0.0);
+        }
+    }
+
+    const FILTERS: [Filter; 4] = [
+        Filter::Nearest,
+        Filter::Bilinear,
+        Filter::Bicubic,
+        Filter::Lanczos3,
+    ];
+
+    /// A one-row opaque strip of linear greys.
+    fn greys(values: &[u8]) -> BufImage {
+        let data = values.iter().flat_map(|&v| [v, v, v, 255]).collect();
+        BufImage::from_rgba8(values.len(), 1, data)
+            .with_color_space(ColorSpace::Linear)
+            .with_edge(EdgeMode::Clamp)
+    }
+
+    #[test]
+    fn nearest_picks_the_texel_under_the_point() {
+        let image = greys(&[0, 255]);
+        assert_eq!(image.get(0.0, 0.5).r, 0.0);
+        assert_eq!(image.get(0.99, 0.5).r, 0.0);
+        assert_eq!(image.get(1.0, 0.5).r, 1.0);
+        assert_eq!(image.get(1.99, 0.5).r, 1.0);
+    }
+
+    #[test]
+    fn bilinear_interpolates_between_texel_centres() {
+        let image = greys(&[0, 255]).with_filter(Filter::Bilinear);
+        for (x, expected) in [
+            (0.5, 0.0),
+            (0.75, 0.25),
+            (1.0, 0.5),
+            (1.25, 0.75),
+            (1.5, 1.0),
+        ] {
+            assert!((image.get(x, 0.5).r - expected).abs() < 1e-6, "at {x}");
+        }
+    }
+
+    #[test]
+    fn filters_reproduce_texels_at_their_centres() {
+        let values = [10, 200, 30, 90, 250, 0];
+        for filter in FILTERS {
+            let image = greys(&values).with_filter(filter);
+            for (i, &v) in values.iter().enumerate() {
+                let got = image.get(i as f32 + 0.5, 0.5).r;
+                assert!(
+                    (got - v as f32 / 255.0).abs() < 1e-5,
+                    "{filter:?} at {i}: {got}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn filters_keep_flat_areas_flat_and_steps_in_gamut() {
+        for filter in FILTERS {
+            let flat = greys(&[100; 8]).with_filter(filter);
+            let step = greys(&[0, 0, 0, 0, 255, 255, 255, 255]).with_filter(filter);
+            for i in 0..80 {
+                let x = i as f32 / 10.0;
+                let p = flat.get(x, 0.5);
+                assert!(
+                    (p.r - 100.0 / 255.0).abs() < 1e-5 && (p.a - 1.0).abs() < 1e-5,
+                    "{filter:?} at {x}: {p:?}"
+                );
+                let p = step.get(x, 0.5);
+                assert!(
+                    (0.0..=1.0).contains(&p.r),
+                    "{filter:?} overshoots at {x}: {}",
+                    p.r
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_are_the_textbook_ones() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-6;
+        let bicubic = |t| Filter::Bicubic.weight(t);
+        assert!(close(bicubic(0.0), 1.0) && close(bicubic(1.0), 0.0) && close(bicubic(2.0), 0.0));
+        assert!(close(bicubic(0.5), 0.5625) && close(bicubic(-1.5), -0.0625));
+        let lanczos = |t| Filter::Lanczos3.weight(t);
+        assert!(close(lanczos(0.0), 1.0));
+        assert!([1.0, 2.0, 3.0, 3.5]
+            .into_iter()
+            .all(|t| close(lanczos(t), 0.0)));
+        assert!(close(Filter::Bilinear.weight(0.25), 0.75));
+    }
+
+    /// A `size × size` black and white checkerboard of single texels.
+    fn checkerboard(size: usize) -> BufImage {
+        let data = (0..size * size)
+            .flat_map(|i| {
+                let v = if (i % size + i / size).is_multiple_of(2) {
+                    0
+                } else {
+                    255
+                };
+                [v, v, v, 255]
+            })
+            .collect();
+        BufImage::from_rgba8(size, size, data)
+    }
+
+    #[test]
+    fn mipmaps_average_a_minified_checkerboard() {
+        for mipmap in [Mipmap::Nearest, Mipmap::Linear] {
+            let image = checkerboard(16).with_mipmaps(mipmap).scale(0.25, 0.25);
+            let rendered = image.render(4, 4);
+            assert!(
+                rendered.chunks(4).all(|p| p == [188, 188, 188, 255]),
+                "{mipmap:?}: {rendered:?}"
+            );
+        }
+        let aliased = checkerboard(16).scale(0.25, 0.25).render(4, 4);
+        assert!(aliased.chunks(4).all(|p| p[0] == 0 || p[0] == 255));
+    }
+
+    #[test]
+    fn mip_levels_follow_the_footprint() {
+        // Each level reports its number and the coordinates it was asked for.
+        let level = |n: usize, x: f32, y: f32| PremulPixel {
+            r: n as f32,
+            g: x,
+            b: y,
+            a: 1.0,
+        };
+        let pick = |mipmap, footprint| sample_mipmapped(mipmap, 4, 8.0, 4.0, footprint, level);
+
+        assert_eq!(pick(Mipmap::Linear, 0.5).r, 0.0);
+        assert_eq!(pick(Mipmap::None, 16.0).r, 0.0);
+        let p = pick(Mipmap::Nearest, 4.0);
+        assert_eq!((p.r, p.g, p.b), (2.0, 2.0, 1.0));
+        assert_eq!(pick(Mipmap::Nearest, 3.0).r, 2.0);
+        assert_eq!(pick(Mipmap::Nearest, 2.5).r, 1.0);
+        let p = pick(Mipmap::Linear, 8.0_f32.sqrt());
+        assert!((p.r - 1.5).abs() < 1e-5, "{p:?}");
+        // Footprints past the smallest level stay on it.
+        assert_eq!(pick(Mipmap::Linear, 1024.0).r, 4.0);
+        assert_eq!(pick(Mipmap::Nearest, 1024.0).r, 4.0);
+    }
+
+    #[test]
+    fn edge_modes_wrap_indices() {
+        let wrap =
+            |edge: EdgeMode| -> Vec<Option<usize>> { (-5..8).map(|i| edge.wrap(i, 3)).collect() };
+        let some = |v: &[usize]| v.iter().map(|&i| Some(i)).collect::<Vec<_>>();
+        let inside = |i: i64| (0..3).contains(&i).then_some(i as usize);
+        assert_eq!(
+            wrap(EdgeMode::Transparent),
+            (-5..8).map(inside).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            wrap(EdgeMode::Border(Pixel::TRANSPARENT)),
+            wrap(EdgeMode::Transparent)
+        );
+        assert_eq!(
+            wrap(EdgeMode::Clamp),
+            some(&[0, 0, 0, 0, 0, 0, 1, 2, 2, 2, 2, 2, 2])
+        );
+        assert_eq!(
+            wrap(EdgeMode::Repeat),
+            some(&[1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1])
+        );
+        assert_eq!(
+            wrap(EdgeMode::Mirror),
+            some(&[1, 2, 2, 1, 0, 0, 1, 2, 2, 1, 0, 0, 1])
+        );
+        assert_eq!(EdgeMode::Clamp.wrap(0, 0), None);
+    }
+
+    #[test]
+    fn edge_modes_fill_outside_the_raster() {
+        let red = Pixel::from_srgb(1.0, 0.0, 0.0, 1.0);
+        let strip = |edge| {
+            greys(&[0, 255])
+                .with_edge(edge)
+                .render(6, 1)
+                .chunks(4)
+                .map(|p| p[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(strip(EdgeMode::Clamp), [0, 255, 255, 255, 255, 255]);
+        assert_eq!(strip(EdgeMode::Repeat), [0, 255, 0, 255, 0, 255]);
+        assert_eq!(strip(EdgeMode::Mirror), [0, 255, 255, 0, 0, 255]);
+
+        let bordered = greys(&[0, 255]).with_edge(EdgeMode::Border(red));
+        assert_eq!(bordered.get(-0.5, 0.5), red);
+        assert_eq!(bordered.get(0.5, 3.5), red);
+        assert_eq!(bordered.bounds(), None);
+        assert_eq!(
+            greys(&[0]).with_edge(EdgeMode::Transparent).get(1.5, 0.5).a,
+            0.0
+        );
+    }
+}
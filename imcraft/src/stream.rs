@@ -0,0 +1,135 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use image::{ImageFormat, Rgba};
+
+use crate::dither::Ditherer;
+use crate::{render, Error, Image, RenderOptions};
+
+/// How many rows are rendered and handed to the encoder at a time, which
+/// bounds memory to `BAND_ROWS × width` pixels whatever the output height.
+const BAND_ROWS: usize = 64;
+
+pub(crate) fn write_streamed<I: Image + ?Sized>(
+    image: &I,
+    path: &Path,
+    width: usize,
+    height: usize,
+    options: &RenderOptions,
+) -> Result<(), Error> {
+    let (w, h) = Error::check_dimensions(width, height)?;
+    match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Png) => write_png(image, path, (w, h), options),
+        Ok(ImageFormat::Tiff) => write_tiff(image, path, (w, h), options),
+        _ => Err(Error::unsupported_output(path)),
+    }
+}
+
+/// Calls `band` with each consecutive block of at most `BAND_ROWS` rendered rows.
+fn for_each_band<I: Image + ?Sized>(
+    image: &I,
+    (width, height): (u32, u32),
+    options: &RenderOptions,
+    mut band: impl FnMut(&[u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let (width, height) = (width as usize, height as usize);
+    let mut buf = vec![0; width * 4 * BAND_ROWS.min(height)];
+    let mut dither = Ditherer::new(options.dither, width);
+    for start in (0..height).step_by(BAND_ROWS) {
+        let rows = start..(start + BAND_ROWS).min(height);
+        let buf = &mut buf[..rows.len() * width * 4];
+        for (y, row) in rows.zip(buf.chunks_exact_mut(width * 4)) {
+            render::render_row::<_, Rgba<u8>>(image, y, height, options, &mut dither, row);
+        }
+        band(buf)?;
+    }
+    Ok(())
+}
+
+fn write_png<I: Image + ?Sized>(
+    image: &I,
+    path: &Path,
+    size: (u32, u32),
+    options: &RenderOptions,
+) -> Result<(), Error> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, size.0, size.1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(Error::png)?;
+    let mut stream = writer.stream_writer().map_err(Error::png)?;
+    for_each_band(image, size, options, |band| Ok(stream.write_all(band)?))?;
+    stream.finish().map_err(Error::png)
+}
+
+fn write_tiff<I: Image + ?Sized>(
+    image: &I,
+    path: &Path,
+    size: (u32, u32),
+    options: &RenderOptions,
+) -> Result<(), Error> {
+    use tiff::encoder::{colortype::RGBA8, TiffEncoder};
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = TiffEncoder::new(file).map_err(Error::tiff)?;
+    let mut tiff = encoder
+        .new_image::<RGBA8>(size.0, size.1)
+        .map_err(Error::tiff)?;
+    tiff.rows_per_strip(BAND_ROWS as u32).map_err(Error::tiff)?;
+    for_each_band(image, size, options, |band| {
+        tiff.write_strip(band).map_err(Error::tiff)
+    })?;
+    tiff.finish().map_err(Error::tiff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BufImage, Dither, EdgeMode, Filter};
+
+    fn gradient() -> impl Image {
+        let data = vec![
+            0, 40, 200, 255, 255, 90, 10, 128, 30, 255, 60, 200, 120, 5, 250, 255,
+        ];
+        BufImage::from_rgba8(2, 2, data)
+            .with_filter(Filter::Bilinear)
+            .with_edge(EdgeMode::Clamp)
+            .scale(50.0, 80.0)
+    }
+
+    #[test]
+    fn streamed_files_match_the_buffered_render() {
+        let (width, height) = (100, 2 * BAND_ROWS + 22);
+        for dither in [Dither::None, Dither::Bayer, Dither::FloydSteinberg] {
+            let options = RenderOptions {
+                dither,
+                ..Default::default()
+            };
+            let expected = gradient().render_with(width, height, &options);
+            for extension in ["png", "tiff"] {
+                let path = std::env::temp_dir().join(format!(
+                    "imcraft-streamed-{}-{dither:?}.{extension}",
+                    std::process::id()
+                ));
+                write_streamed(&gradient(), &path, width, height, &options).unwrap();
+                let decoded = image::open(&path).map(|image| image.into_rgba8());
+                std::fs::remove_file(&path).unwrap();
+                let decoded = decoded.unwrap();
+                assert_eq!(decoded.dimensions(), (width as u32, height as u32));
+                assert!(
+                    decoded.as_raw() == &expected,
+                    "{extension} with {dither:?} differs from render_with"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn other_extensions_are_rejected() {
+        let path = std::env::temp_dir().join("imcraft-streamed.jpg");
+        let result = write_streamed(&gradient(), &path, 4, 4, &RenderOptions::default());
+        assert!(matches!(result, Err(Error::UnsupportedFormat(_))));
+        assert!(!path.exists());
+    }
+}